@@ -3,7 +3,7 @@ use std::time::Duration;
 use simple_error::SimpleResult;
 use smol::MainExecutor as _;
 use smol::{Executor, Timer};
-use smol_otel::{Counter, Gauge, OtlpTracer};
+use smol_otel::{Attribute, Counter, Gauge, Histogram, MeterProvider, OtlpTracer};
 
 async fn async_main(executor: Arc<Executor<'static>>) -> SimpleResult<()> {
     // init logger
@@ -19,43 +19,64 @@ async fn async_main(executor: Arc<Executor<'static>>) -> SimpleResult<()> {
     // register globals
     smol_otel::globals::register(executor.clone(), tracer.clone());
 
+    // create meter provider exporting every 2 seconds
+    let meter_provider = Arc::new(MeterProvider::new(tracer.clone(), Duration::from_secs(2)));
+
     // Create a counter for processed orders
-    let orders_processed = Counter::new(
+    let orders_processed = Arc::new(Counter::new(
         tracer.clone(),
         "orders_processed",
         "Number of orders processed",
         "orders",
     )
     .with_attribute("customer.id", "customer456")
-    .with_attribute("order.status", "success")
-    .with_attribute("order.type", "standard");
+    .with_attribute("order.type", "standard"));
 
     // Create a gauge for current queue size
-    let queue_size = Gauge::new(
+    let queue_size = Arc::new(Gauge::new(
         tracer.clone(),
         "order_queue_size",
         "Current number of orders in queue",
         "orders",
     )
-    .with_attribute("queue.type", "standard");
+    .with_attribute("queue.type", "standard"));
+
+    // Create a histogram for order processing latency
+    let processing_time = Arc::new(Histogram::new(
+        tracer.clone(),
+        "order_processing_time",
+        "Time taken to process an order",
+        "ms",
+    )
+    .with_boundaries(vec![10.0, 50.0, 100.0, 250.0, 500.0, 1000.0]));
+
+    // register instruments and start periodic export
+    meter_provider.register(&orders_processed);
+    meter_provider.register(&queue_size);
+    meter_provider.register(&processing_time);
+    meter_provider.start(&executor);
 
     // Simulate some metrics
     for i in 0..5 {
         // Increment processed orders
-        orders_processed.inc();
-        orders_processed.upload().await?;
-        log::info!("Order processed: {}", i + 1);
+        let status = if i % 2 == 0 { "success" } else { "failed" };
+        orders_processed.add(1, &[Attribute::new("order.status", status)]);
+        log::info!(order_id = i + 1, status; "Order processed");
 
         // Update queue size
-        queue_size.set(10 - i);
-        queue_size.upload().await?;
+        queue_size.set(10 - i, &[]);
         log::info!("Queue size updated: {}", 10 - i);
 
+        // Record processing latency
+        processing_time.record(42.0 * (i + 1) as f64);
+
         Timer::after(Duration::from_secs(1)).await;
     }
 
     log::info!("Metrics example completed!");
-    Timer::never().await;
+    // stops the meter provider too, with a final collection of every instrument
+    let report = tracer.shutdown(Duration::from_secs(10)).await;
+    log::info!("exported {} items, {} failed", report.exported, report.failed);
 
     Ok(())
 }
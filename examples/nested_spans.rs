@@ -1,9 +1,10 @@
 use std::sync::Arc;
+use std::time::Duration;
 
 use simple_error::SimpleResult;
 use smol::MainExecutor as _;
-use smol::{Executor, Timer};
-use smol_otel::{globals, OtlpTracer, SpanKind, StatusCode};
+use smol::Executor;
+use smol_otel::{globals, Instrument as _, OtlpTracer, ResourceDetector, SpanKind, StatusCode};
 
 async fn do_work3() -> SimpleResult<()> {
     let guard = globals::tracer()
@@ -42,8 +43,12 @@ async fn async_main(executor: Arc<Executor<'static>>) -> SimpleResult<()> {
     // create tracer
     let traces_endpoint = "https://otlp-gateway-prod-us-east-0.grafana.net/otlp/v1/traces";
     let metrics_endpoint = "https://otlp-gateway-prod-us-east-0.grafana.net/otlp/v1/metrics";
+    let logs_endpoint = "https://otlp-gateway-prod-us-east-0.grafana.net/otlp/v1/logs";
     let service_name = "smol_tracer";
-    let tracer = OtlpTracer::new(traces_endpoint, metrics_endpoint, service_name)?;
+    let tracer = OtlpTracer::new(traces_endpoint, metrics_endpoint, service_name)?
+        .with_logs_endpoint(logs_endpoint)?
+        .with_service_version(env!("CARGO_PKG_VERSION"))
+        .with_resource_detectors(&ResourceDetector::ALL);
     let tracer = Arc::new(tracer);
 
     // register globals
@@ -58,14 +63,15 @@ async fn async_main(executor: Arc<Executor<'static>>) -> SimpleResult<()> {
     // log
     log::info!("hello, world!");
 
-    // do work
-    do_work1().await?;
+    // do work on another executor task, keeping async_main as its parent
+    executor.spawn(do_work1().with_current_context()).await?;
 
     // drop
     drop(guard);
 
-    // wait forever
-    Timer::never().await;
+    // flush pending spans before exiting
+    let report = tracer.shutdown(Duration::from_secs(10)).await;
+    log::info!("exported {} spans, {} failed", report.exported, report.failed);
 
     // return
     Ok(())
@@ -0,0 +1,276 @@
+use std::future::Future;
+use std::sync::Arc;
+use std::sync::Mutex as SyncMutex;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use smol::{Executor, Task, Timer};
+
+#[derive(Clone, Debug)]
+pub struct BatchConfig {
+    /// Maximum number of items buffered before new ones are dropped
+    pub max_queue_size: usize,
+    /// Maximum number of items sent in a single export request
+    pub max_export_batch_size: usize,
+    /// Interval at which queued items are flushed regardless of batch size
+    pub scheduled_delay: Duration,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        Self {
+            max_queue_size: 2048,
+            max_export_batch_size: 512,
+            scheduled_delay: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Outcome of draining pending exports during a flush or shutdown
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FlushReport {
+    /// Items successfully delivered to the collector
+    pub exported: usize,
+    /// Items that failed to upload or did not finish before the deadline
+    pub failed: usize,
+}
+
+impl FlushReport {
+    pub(crate) fn record(&mut self, items: usize, success: bool) {
+        if success {
+            self.exported += items;
+        } else {
+            self.failed += items;
+        }
+    }
+
+    pub(crate) fn merge(&mut self, other: FlushReport) {
+        self.exported += other.exported;
+        self.failed += other.failed;
+    }
+}
+
+/// Runs `future` until `deadline`, returning `None` if the deadline passed first
+pub(crate) async fn with_deadline<F: Future>(deadline: Instant, future: F) -> Option<F::Output> {
+    smol::future::or(
+        async { Some(future.await) },
+        async {
+            Timer::at(deadline).await;
+            None
+        },
+    ).await
+}
+
+/// An export task spawned on the executor together with the number of items it carries
+struct PendingExport {
+    items: usize,
+    task: Task<()>,
+}
+
+#[derive(Default)]
+pub(crate) struct PendingExports {
+    exports: SyncMutex<Vec<PendingExport>>,
+    /// Outcomes recorded by the export tasks themselves, so exports that finish before a flush are still reported
+    completed: Arc<SyncMutex<FlushReport>>,
+}
+
+impl PendingExports {
+    /// Spawns `export` on `executor`, the future resolves to whether its `items` were delivered
+    pub fn spawn<F>(&self, executor: &Executor<'static>, items: usize, export: F)
+    where
+        F: Future<Output = bool> + Send + 'static,
+    {
+        let completed = self.completed.clone();
+        let task = executor.spawn(async move {
+            let success = export.await;
+            completed.lock().unwrap().record(items, success);
+        });
+
+        let mut exports = self.exports.lock().unwrap();
+        // Finished tasks already recorded their outcome, only keep in-flight ones
+        exports.retain(|export| !export.task.is_finished());
+        exports.push(PendingExport { items, task });
+    }
+
+    /// Awaits every tracked export, including ones spawned while waiting, cancelling those still running at `deadline`
+    pub async fn drain(&self, deadline: Instant) -> FlushReport {
+        let mut report = FlushReport::default();
+        loop {
+            let exports = std::mem::take(&mut *self.exports.lock().unwrap());
+            if exports.is_empty() {
+                break;
+            }
+            for mut export in exports {
+                if with_deadline(deadline, &mut export.task).await.is_none() {
+                    // A task that finished while being cancelled has recorded its own outcome
+                    if export.task.cancel().await.is_none() {
+                        report.record(export.items, false);
+                    }
+                }
+            }
+        }
+        report.merge(std::mem::take(&mut *self.completed.lock().unwrap()));
+        report
+    }
+}
+
+impl std::fmt::Debug for PendingExports {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PendingExports")
+            .field("len", &self.exports.lock().unwrap().len())
+            .finish()
+    }
+}
+
+pub(crate) struct BatchQueue<T> {
+    config: BatchConfig,
+    items: SyncMutex<Vec<T>>,
+    dropped: AtomicU64,
+    /// Periodic flush task, started with the first item
+    timer: SyncMutex<Option<Task<()>>>,
+    /// Set while a background export of this queue is running
+    exporting: AtomicBool,
+    closed: AtomicBool,
+}
+
+impl<T> BatchQueue<T> {
+    pub fn new(config: BatchConfig) -> Self {
+        Self {
+            config,
+            items: SyncMutex::new(Vec::new()),
+            dropped: AtomicU64::new(0),
+            timer: SyncMutex::new(None),
+            exporting: AtomicBool::new(false),
+            closed: AtomicBool::new(false),
+        }
+    }
+
+    pub fn config(&self) -> &BatchConfig {
+        &self.config
+    }
+
+    /// Queues an item, returning true once a full batch is ready to export
+    pub fn push(&self, item: T) -> bool {
+        let mut items = self.items.lock().unwrap();
+        if self.is_closed() || items.len() >= self.config.max_queue_size {
+            self.dropped.fetch_add(1, Ordering::SeqCst);
+            return false;
+        }
+        items.push(item);
+        items.len() >= self.config.max_export_batch_size
+    }
+
+    /// Removes up to `max_export_batch_size` items from the front of the queue
+    pub fn next_batch(&self) -> Vec<T> {
+        let mut items = self.items.lock().unwrap();
+        let count = items.len().min(self.config.max_export_batch_size);
+        items.drain(..count).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.lock().unwrap().len()
+    }
+
+    pub fn dropped_count(&self) -> u64 {
+        self.dropped.load(Ordering::SeqCst)
+    }
+
+    /// Claims the single background export slot, returning false if an export is already running
+    pub fn start_export(&self) -> bool {
+        !self.exporting.swap(true, Ordering::SeqCst)
+    }
+
+    pub fn finish_export(&self) {
+        self.exporting.store(false, Ordering::SeqCst);
+    }
+
+    /// Stops accepting new items; anything pushed afterwards is counted as dropped
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    /// Starts the flush timer with `spawn` unless it is already running or the queue is closed
+    pub fn start_timer(&self, spawn: impl FnOnce() -> Task<()>) {
+        let mut timer = self.timer.lock().unwrap();
+        if timer.is_none() && !self.is_closed() {
+            *timer = Some(spawn());
+        }
+    }
+
+    /// Cancels the flush timer, so it cannot start exports behind the back of a shutdown
+    pub async fn stop_timer(&self) {
+        let timer = self.timer.lock().unwrap().take();
+        if let Some(timer) = timer {
+            timer.cancel().await;
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for BatchQueue<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BatchQueue")
+            .field("config", &self.config)
+            .field("len", &self.len())
+            .field("dropped", &self.dropped_count())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drain_reports_exports_that_finished_before_the_flush() {
+        let executor = Executor::new();
+        let pending = PendingExports::default();
+        smol::block_on(executor.run(async {
+            pending.spawn(&executor, 3, async { true });
+            pending.spawn(&executor, 2, async { false });
+            Timer::after(Duration::from_millis(20)).await;
+
+            // Tracking new exports prunes the finished ones, their outcomes must survive that
+            pending.spawn(&executor, 4, async { true });
+            pending.spawn(&executor, 5, std::future::pending());
+
+            let report = pending.drain(Instant::now() + Duration::from_millis(50)).await;
+            assert_eq!(report, FlushReport { exported: 7, failed: 7 });
+        }));
+    }
+
+    #[test]
+    fn drain_waits_for_exports_spawned_while_draining() {
+        let executor = Arc::new(Executor::new());
+        let pending = Arc::new(PendingExports::default());
+        smol::block_on(executor.run(async {
+            let (executor_clone, pending_clone) = (executor.clone(), pending.clone());
+            pending.spawn(&executor, 1, async move {
+                Timer::after(Duration::from_millis(10)).await;
+                pending_clone.spawn(&executor_clone, 2, async {
+                    Timer::after(Duration::from_millis(10)).await;
+                    true
+                });
+                true
+            });
+
+            let report = pending.drain(Instant::now() + Duration::from_secs(1)).await;
+            assert_eq!(report, FlushReport { exported: 3, failed: 0 });
+        }));
+    }
+
+    #[test]
+    fn drain_is_empty_once_reported() {
+        let executor = Executor::new();
+        let pending = PendingExports::default();
+        smol::block_on(executor.run(async {
+            pending.spawn(&executor, 1, async { true });
+            let deadline = Instant::now() + Duration::from_secs(1);
+            assert_eq!(pending.drain(deadline).await, FlushReport { exported: 1, failed: 0 });
+            assert_eq!(pending.drain(deadline).await, FlushReport::default());
+        }));
+    }
+}
@@ -0,0 +1,123 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex as SyncMutex;
+use std::time::{Duration, Instant};
+
+use http::Uri;
+
+struct IdleConnection {
+    stream: Box<dyn Any + Send>,
+    idle_since: Instant,
+}
+
+/// Keep-alive connections per endpoint. Streams are stored type-erased so the pool does not depend on the
+/// HTTP client's stream type, callers get it back through inference at the checkout site.
+pub(crate) struct ConnectionPool {
+    idle: SyncMutex<HashMap<String, Vec<IdleConnection>>>,
+    max_idle_per_endpoint: usize,
+    /// Idle connections older than this are assumed to have been closed by the server
+    idle_timeout: Duration,
+}
+
+impl ConnectionPool {
+    pub fn new(max_idle_per_endpoint: usize, idle_timeout: Duration) -> Self {
+        Self {
+            idle: SyncMutex::new(HashMap::new()),
+            max_idle_per_endpoint,
+            idle_timeout,
+        }
+    }
+
+    pub fn max_idle_per_endpoint(&self) -> usize {
+        self.max_idle_per_endpoint
+    }
+
+    pub fn idle_timeout(&self) -> Duration {
+        self.idle_timeout
+    }
+
+    /// Connections are shared between URIs with the same scheme and authority
+    pub fn key(uri: &Uri) -> String {
+        format!(
+            "{}://{}",
+            uri.scheme_str().unwrap_or("http"),
+            uri.authority().map(|authority| authority.as_str()).unwrap_or_default(),
+        )
+    }
+
+    /// Takes the most recently used idle connection for `key`, discarding expired ones
+    pub fn checkout<S: Send + 'static>(&self, key: &str) -> Option<S> {
+        let mut idle = self.idle.lock().unwrap();
+        let connections = idle.get_mut(key)?;
+        connections.retain(|connection| connection.idle_since.elapsed() < self.idle_timeout);
+        let connection = connections.pop()?;
+        connection.stream.downcast::<S>().ok().map(|stream| *stream)
+    }
+
+    /// Returns a connection that is still usable, dropping it if the endpoint already has enough idle ones
+    pub fn checkin<S: Send + 'static>(&self, key: String, stream: S) {
+        let mut idle = self.idle.lock().unwrap();
+        let connections = idle.entry(key).or_default();
+        if connections.len() < self.max_idle_per_endpoint {
+            connections.push(IdleConnection {
+                stream: Box::new(stream),
+                idle_since: Instant::now(),
+            });
+        }
+    }
+}
+
+impl fmt::Debug for ConnectionPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let idle: usize = self.idle.lock().map(|idle| idle.values().map(Vec::len).sum()).unwrap_or_default();
+        f.debug_struct("ConnectionPool")
+            .field("idle", &idle)
+            .field("max_idle_per_endpoint", &self.max_idle_per_endpoint)
+            .field("idle_timeout", &self.idle_timeout)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "http://localhost:4318";
+
+    #[test]
+    fn checkout_returns_most_recent_connection() {
+        let pool = ConnectionPool::new(2, Duration::from_secs(30));
+        pool.checkin(KEY.to_string(), 1u32);
+        pool.checkin(KEY.to_string(), 2u32);
+        assert_eq!(pool.checkout::<u32>(KEY), Some(2));
+        assert_eq!(pool.checkout::<u32>(KEY), Some(1));
+        assert_eq!(pool.checkout::<u32>(KEY), None);
+    }
+
+    #[test]
+    fn keeps_at_most_max_idle_connections() {
+        let pool = ConnectionPool::new(1, Duration::from_secs(30));
+        pool.checkin(KEY.to_string(), 1u32);
+        pool.checkin(KEY.to_string(), 2u32);
+        assert_eq!(pool.checkout::<u32>(KEY), Some(1));
+        assert_eq!(pool.checkout::<u32>(KEY), None);
+    }
+
+    #[test]
+    fn discards_connections_past_idle_timeout() {
+        let pool = ConnectionPool::new(2, Duration::from_millis(10));
+        pool.checkin(KEY.to_string(), 1u32);
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(pool.checkout::<u32>(KEY), None);
+    }
+
+    #[test]
+    fn keys_by_scheme_and_authority() {
+        let traces: Uri = "http://collector:4318/v1/traces".parse().unwrap();
+        let metrics: Uri = "http://collector:4318/v1/metrics".parse().unwrap();
+        let other: Uri = "https://collector:4318/v1/traces".parse().unwrap();
+        assert_eq!(ConnectionPool::key(&traces), ConnectionPool::key(&metrics));
+        assert_ne!(ConnectionPool::key(&traces), ConnectionPool::key(&other));
+    }
+}
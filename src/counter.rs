@@ -1,14 +1,16 @@
 use std::sync::Arc;
-use std::time::SystemTime;
 
+use crate::meter_provider::Collect;
+use crate::structs::{AnyValue, Attribute, Metric, Temporality};
 use crate::tracer::OtlpTracer;
 use crate::metric_base::MetricBase;
+use crate::number::Number;
 
-pub struct Counter {
-    base: MetricBase,
+pub struct Counter<T: Number = i64> {
+    base: MetricBase<T>,
 }
 
-impl Counter {
+impl Counter<i64> {
     pub fn new(
         tracer: Arc<OtlpTracer>,
         name: &str,
@@ -19,27 +21,58 @@ impl Counter {
             base: MetricBase::new(tracer, name, description, unit),
         }
     }
+}
 
-    pub fn with_attribute(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+impl Counter<f64> {
+    pub fn new_f64(
+        tracer: Arc<OtlpTracer>,
+        name: &str,
+        description: &str,
+        unit: &str,
+    ) -> Self {
+        Self {
+            base: MetricBase::new(tracer, name, description, unit),
+        }
+    }
+}
+
+impl<T: Number> Counter<T> {
+    pub fn with_attribute(mut self, key: impl Into<String>, value: impl Into<AnyValue>) -> Self {
         self.base.with_attribute(key, value);
         self
     }
 
+    pub fn with_temporality(mut self, temporality: Temporality) -> Self {
+        self.base.with_temporality(temporality);
+        self
+    }
+
+    /// Caps the number of distinct attribute sets, further ones are aggregated into an overflow series
+    pub fn with_cardinality_limit(mut self, limit: usize) -> Self {
+        self.base.with_cardinality_limit(limit);
+        self
+    }
+
     pub fn inc(&self) {
-        self.add(1);
+        self.add(T::ONE, &[]);
     }
 
-    pub fn add(&self, value: i64) {
-        self.base.add_value(value);
+    /// Adds `value` to the series identified by `attributes`
+    pub fn add(&self, value: T, attributes: &[Attribute]) {
+        // Counters are monotonic, negative increments are ignored
+        if value < T::ZERO {
+            return;
+        }
+        self.base.add_value(value, attributes);
     }
 
     pub async fn upload(&self) -> simple_error::SimpleResult<()> {
-        let start_time = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .unwrap()
-            .as_nanos()
-            .to_string();
+        self.base.upload(self.collect()).await
+    }
+}
 
-        self.base.upload_metric(start_time, true, 2).await // 2 = Cumulative
+impl<T: Number> Collect for Counter<T> {
+    fn collect(&self) -> Metric {
+        self.base.sum_metric(true)
     }
 }
\ No newline at end of file
@@ -0,0 +1,316 @@
+use std::env;
+use std::time::Duration;
+
+use crate::tracer::{Compression, ExportConfig, Protocol};
+
+/// Default collector address for OTLP over HTTP
+const DEFAULT_ENDPOINT: &str = "http://localhost:4318";
+
+const DEFAULT_SERVICE_NAME: &str = "unknown_service";
+
+/// Telemetry signal, used to pick signal specific `OTEL_EXPORTER_OTLP_*` variables
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Signal {
+    Traces,
+    Metrics,
+    Logs,
+}
+
+impl Signal {
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Signal::Traces => "traces",
+            Signal::Metrics => "metrics",
+            Signal::Logs => "logs",
+        }
+    }
+
+    fn env_name(&self) -> &'static str {
+        match self {
+            Signal::Traces => "TRACES",
+            Signal::Metrics => "METRICS",
+            Signal::Logs => "LOGS",
+        }
+    }
+
+    fn path(&self) -> &'static str {
+        match self {
+            Signal::Traces => "/v1/traces",
+            Signal::Metrics => "/v1/metrics",
+            Signal::Logs => "/v1/logs",
+        }
+    }
+}
+
+/// Looks a variable up in the process environment
+fn process_env(name: &str) -> Option<String> {
+    env::var(name).ok()
+}
+
+/// Reads a variable through `lookup`, treating empty values as unset like the specification asks
+fn var(lookup: &impl Fn(&str) -> Option<String>, name: &str) -> Option<String> {
+    lookup(name)
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+/// Signal specific `OTEL_EXPORTER_OTLP_<SIGNAL>_<option>` if set, otherwise `OTEL_EXPORTER_OTLP_<option>`
+fn signal_var(lookup: &impl Fn(&str) -> Option<String>, signal: Signal, option: &str) -> Option<String> {
+    var(lookup, &format!("OTEL_EXPORTER_OTLP_{}_{}", signal.env_name(), option))
+        .or_else(|| var(lookup, &format!("OTEL_EXPORTER_OTLP_{}", option)))
+}
+
+pub(crate) fn endpoint(signal: Signal) -> String {
+    endpoint_from(signal, process_env)
+}
+
+/// The signal specific endpoint is used as-is, the general one gets the signal path appended
+fn endpoint_from(signal: Signal, lookup: impl Fn(&str) -> Option<String>) -> String {
+    if let Some(endpoint) = var(&lookup, &format!("OTEL_EXPORTER_OTLP_{}_ENDPOINT", signal.env_name())) {
+        return endpoint;
+    }
+    let base = var(&lookup, "OTEL_EXPORTER_OTLP_ENDPOINT").unwrap_or(DEFAULT_ENDPOINT.to_string());
+    format!("{}{}", base.trim_end_matches('/'), signal.path())
+}
+
+pub(crate) fn export_config(signal: Signal) -> ExportConfig {
+    export_config_from(signal, process_env)
+}
+
+fn export_config_from(signal: Signal, lookup: impl Fn(&str) -> Option<String>) -> ExportConfig {
+    let mut config = ExportConfig::default();
+
+    // Signal specific headers are merged over the general ones, replacing keys present in both
+    let mut headers = var(&lookup, "OTEL_EXPORTER_OTLP_HEADERS").map(|value| parse_key_values(&value)).unwrap_or_default();
+    if let Some(value) = var(&lookup, &format!("OTEL_EXPORTER_OTLP_{}_HEADERS", signal.env_name())) {
+        for (key, value) in parse_key_values(&value) {
+            headers.retain(|(existing, _)| !existing.eq_ignore_ascii_case(&key));
+            headers.push((key, value));
+        }
+    }
+    config.headers = headers;
+
+    if let Some(timeout) = signal_var(&lookup, signal, "TIMEOUT").and_then(|value| parse_timeout(&value)) {
+        config.timeout = timeout;
+    }
+
+    if let Some(value) = signal_var(&lookup, signal, "COMPRESSION") {
+        match value.as_str() {
+            "gzip" => config.compression = Compression::Gzip,
+            "zstd" if cfg!(feature = "zstd") => config.compression = Compression::Zstd,
+            "none" => config.compression = Compression::None,
+            other => eprintln!("Ignoring unsupported OTLP compression {:?}", other),
+        }
+    }
+
+    if let Some(value) = signal_var(&lookup, signal, "PROTOCOL") {
+        match value.as_str() {
+            "http/json" => config.protocol = Protocol::HttpJson,
+            "http/protobuf" => config.protocol = Protocol::HttpProtobuf,
+            other => eprintln!("Ignoring unsupported OTLP protocol {:?}", other),
+        }
+    }
+
+    config
+}
+
+/// `OTEL_EXPORTER_OTLP_TIMEOUT` in milliseconds, if set and valid
+pub(crate) fn timeout() -> Option<Duration> {
+    var(&process_env, "OTEL_EXPORTER_OTLP_TIMEOUT").and_then(|value| parse_timeout(&value))
+}
+
+fn parse_timeout(value: &str) -> Option<Duration> {
+    match value.parse::<u64>() {
+        Ok(millis) => Some(Duration::from_millis(millis)),
+        Err(e) => {
+            eprintln!("Ignoring invalid OTLP timeout {:?}: {}", value, e);
+            None
+        }
+    }
+}
+
+pub(crate) fn service_name() -> String {
+    service_name_from(process_env)
+}
+
+/// `OTEL_SERVICE_NAME`, falling back to `service.name` in `OTEL_RESOURCE_ATTRIBUTES`
+fn service_name_from(lookup: impl Fn(&str) -> Option<String>) -> String {
+    var(&lookup, "OTEL_SERVICE_NAME")
+        .or_else(|| {
+            resource_attributes_from(&lookup).into_iter()
+                .find(|(key, _)| key == "service.name")
+                .map(|(_, value)| value)
+        })
+        .unwrap_or(DEFAULT_SERVICE_NAME.to_string())
+}
+
+pub(crate) fn resource_attributes() -> Vec<(String, String)> {
+    resource_attributes_from(process_env)
+}
+
+fn resource_attributes_from(lookup: impl Fn(&str) -> Option<String>) -> Vec<(String, String)> {
+    var(&lookup, "OTEL_RESOURCE_ATTRIBUTES").map(|value| parse_key_values(&value)).unwrap_or_default()
+}
+
+pub(crate) fn sdk_disabled() -> bool {
+    sdk_disabled_from(process_env)
+}
+
+fn sdk_disabled_from(lookup: impl Fn(&str) -> Option<String>) -> bool {
+    var(&lookup, "OTEL_SDK_DISABLED").is_some_and(|value| value.eq_ignore_ascii_case("true"))
+}
+
+/// Parses `key1=value1,key2=value2` with percent-encoded values, as used by the headers and resource attributes
+pub(crate) fn parse_key_values(value: &str) -> Vec<(String, String)> {
+    value.split(',')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(key, value)| (key.trim().to_string(), percent_decode(value.trim())))
+        .filter(|(key, _)| !key.is_empty())
+        .collect()
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let is_escape = bytes[i] == b'%'
+            && i + 2 < bytes.len()
+            && bytes[i + 1].is_ascii_hexdigit()
+            && bytes[i + 2].is_ascii_hexdigit();
+        if is_escape {
+            decoded.push((hex_digit(bytes[i + 1]) << 4) | hex_digit(bytes[i + 2]));
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn hex_digit(byte: u8) -> u8 {
+    match byte {
+        b'0'..=b'9' => byte - b'0',
+        b'a'..=b'f' => byte - b'a' + 10,
+        _ => byte - b'A' + 10,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Lookup over a fixed set of variables, so tests never touch the process environment
+    fn vars<'a>(values: &'a [(&'a str, &'a str)]) -> impl Fn(&str) -> Option<String> + 'a {
+        move |name| values.iter().find(|(key, _)| *key == name).map(|(_, value)| value.to_string())
+    }
+
+    #[test]
+    fn signal_endpoint_is_used_as_is() {
+        let lookup = vars(&[
+            ("OTEL_EXPORTER_OTLP_ENDPOINT", "http://collector:4318"),
+            ("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT", "http://traces:4318/custom/"),
+        ]);
+        assert_eq!(endpoint_from(Signal::Traces, &lookup), "http://traces:4318/custom/");
+        assert_eq!(endpoint_from(Signal::Metrics, &lookup), "http://collector:4318/v1/metrics");
+    }
+
+    #[test]
+    fn general_endpoint_gets_signal_path() {
+        let lookup = vars(&[("OTEL_EXPORTER_OTLP_ENDPOINT", "http://collector:4318/")]);
+        assert_eq!(endpoint_from(Signal::Logs, &lookup), "http://collector:4318/v1/logs");
+        assert_eq!(endpoint_from(Signal::Traces, vars(&[])), "http://localhost:4318/v1/traces");
+        assert_eq!(endpoint_from(Signal::Traces, vars(&[("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT", " ")])), "http://localhost:4318/v1/traces");
+    }
+
+    #[test]
+    fn signal_headers_replace_general_ones_ignoring_case() {
+        let lookup = vars(&[
+            ("OTEL_EXPORTER_OTLP_HEADERS", "Authorization=general,x-tenant=a"),
+            ("OTEL_EXPORTER_OTLP_METRICS_HEADERS", "authorization=metrics,x-extra=b"),
+        ]);
+        assert_eq!(export_config_from(Signal::Metrics, &lookup).headers, vec![
+            ("x-tenant".to_string(), "a".to_string()),
+            ("authorization".to_string(), "metrics".to_string()),
+            ("x-extra".to_string(), "b".to_string()),
+        ]);
+        assert_eq!(export_config_from(Signal::Traces, &lookup).headers, vec![
+            ("Authorization".to_string(), "general".to_string()),
+            ("x-tenant".to_string(), "a".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn signal_options_fall_back_to_general_ones() {
+        let lookup = vars(&[
+            ("OTEL_EXPORTER_OTLP_TIMEOUT", "2500"),
+            ("OTEL_EXPORTER_OTLP_LOGS_TIMEOUT", "500"),
+            ("OTEL_EXPORTER_OTLP_COMPRESSION", "gzip"),
+            ("OTEL_EXPORTER_OTLP_TRACES_COMPRESSION", "none"),
+            ("OTEL_EXPORTER_OTLP_PROTOCOL", "http/protobuf"),
+            ("OTEL_EXPORTER_OTLP_METRICS_PROTOCOL", "http/json"),
+        ]);
+
+        let traces = export_config_from(Signal::Traces, &lookup);
+        assert_eq!(traces.timeout, Duration::from_millis(2500));
+        assert_eq!(traces.compression, Compression::None);
+        assert_eq!(traces.protocol, Protocol::HttpProtobuf);
+
+        let metrics = export_config_from(Signal::Metrics, &lookup);
+        assert_eq!(metrics.compression, Compression::Gzip);
+        assert_eq!(metrics.protocol, Protocol::HttpJson);
+
+        let logs = export_config_from(Signal::Logs, &lookup);
+        assert_eq!(logs.timeout, Duration::from_millis(500));
+    }
+
+    #[test]
+    fn invalid_options_keep_defaults() {
+        let lookup = vars(&[
+            ("OTEL_EXPORTER_OTLP_TIMEOUT", "10s"),
+            ("OTEL_EXPORTER_OTLP_COMPRESSION", "brotli"),
+            ("OTEL_EXPORTER_OTLP_PROTOCOL", "grpc"),
+        ]);
+        let config = export_config_from(Signal::Traces, &lookup);
+        let defaults = ExportConfig::default();
+        assert_eq!(config.timeout, defaults.timeout);
+        assert_eq!(config.compression, defaults.compression);
+        assert_eq!(config.protocol, defaults.protocol);
+    }
+
+    #[test]
+    fn zstd_compression_needs_the_feature() {
+        let config = export_config_from(Signal::Traces, vars(&[("OTEL_EXPORTER_OTLP_COMPRESSION", "zstd")]));
+        let expected = if cfg!(feature = "zstd") { Compression::Zstd } else { Compression::None };
+        assert_eq!(config.compression, expected);
+    }
+
+    #[test]
+    fn service_name_env_wins_over_resource_attribute() {
+        let attributes = ("OTEL_RESOURCE_ATTRIBUTES", "service.name=from-attributes,host.name=web-1");
+        assert_eq!(service_name_from(vars(&[attributes, ("OTEL_SERVICE_NAME", "from-env")])), "from-env");
+        assert_eq!(service_name_from(vars(&[attributes])), "from-attributes");
+        assert_eq!(service_name_from(vars(&[])), "unknown_service");
+    }
+
+    #[test]
+    fn decodes_percent_escapes() {
+        assert_eq!(percent_decode("Bearer%20abc%3D%3d"), "Bearer abc==");
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz%4"), "%zz%4");
+        assert_eq!(percent_decode("caf%C3%A9"), "café");
+        assert_eq!(
+            parse_key_values("a=1%2C2, b = x%3Dy ,=ignored,no-value"),
+            vec![("a".to_string(), "1,2".to_string()), ("b".to_string(), "x=y".to_string())],
+        );
+    }
+
+    #[test]
+    fn sdk_disabled_only_for_true() {
+        assert!(sdk_disabled_from(vars(&[("OTEL_SDK_DISABLED", "true")])));
+        assert!(sdk_disabled_from(vars(&[("OTEL_SDK_DISABLED", " TRUE ")])));
+        assert!(!sdk_disabled_from(vars(&[("OTEL_SDK_DISABLED", "1")])));
+        assert!(!sdk_disabled_from(vars(&[])));
+    }
+}
@@ -1,14 +1,16 @@
 use std::sync::Arc;
 
+use crate::meter_provider::Collect;
+use crate::structs::{AnyValue, Attribute, Metric};
 use crate::tracer::OtlpTracer;
 use crate::metric_base::MetricBase;
-use crate::utilities;
+use crate::number::Number;
 
-pub struct Gauge {
-    base: MetricBase,
+pub struct Gauge<T: Number = i64> {
+    base: MetricBase<T>,
 }
 
-impl Gauge {
+impl Gauge<i64> {
     pub fn new(
         tracer: Arc<OtlpTracer>,
         name: &str,
@@ -19,18 +21,45 @@ impl Gauge {
             base: MetricBase::new(tracer, name, description, unit),
         }
     }
+}
+
+impl Gauge<f64> {
+    pub fn new_f64(
+        tracer: Arc<OtlpTracer>,
+        name: &str,
+        description: &str,
+        unit: &str,
+    ) -> Self {
+        Self {
+            base: MetricBase::new(tracer, name, description, unit),
+        }
+    }
+}
 
-    pub fn with_attribute(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+impl<T: Number> Gauge<T> {
+    pub fn with_attribute(mut self, key: impl Into<String>, value: impl Into<AnyValue>) -> Self {
         self.base.with_attribute(key, value);
         self
     }
 
-    pub fn set(&self, value: i64) {
-        self.base.set_value(value);
+    /// Caps the number of distinct attribute sets, further ones are aggregated into an overflow series
+    pub fn with_cardinality_limit(mut self, limit: usize) -> Self {
+        self.base.with_cardinality_limit(limit);
+        self
+    }
+
+    /// Records the current `value` of the series identified by `attributes`
+    pub fn set(&self, value: T, attributes: &[Attribute]) {
+        self.base.set_value(value, attributes);
     }
 
     pub async fn upload(&self) -> simple_error::SimpleResult<()> {
-        let current_time = utilities::nanos().to_string();
-        self.base.upload_metric(current_time, false, 1).await // 1 = Delta
+        self.base.upload(self.collect()).await
+    }
+}
+
+impl<T: Number> Collect for Gauge<T> {
+    fn collect(&self) -> Metric {
+        self.base.gauge_metric()
     }
 }
@@ -18,3 +18,11 @@ pub fn executor() -> &'static Arc<Executor<'static>> {
 pub fn tracer() -> &'static Arc<OtlpTracer> {
     GLOBAL_TRACER.get().expect("Global tracer not initialized")
 }
+
+pub fn try_executor() -> Option<&'static Arc<Executor<'static>>> {
+    GLOBAL_EXECUTOR.get()
+}
+
+pub fn try_tracer() -> Option<&'static Arc<OtlpTracer>> {
+    GLOBAL_TRACER.get()
+}
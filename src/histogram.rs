@@ -0,0 +1,243 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::Mutex as SyncMutex;
+
+use crate::meter_provider::Collect;
+use crate::structs::*;
+use crate::tracer::OtlpTracer;
+use crate::utilities;
+
+/// Default OpenTelemetry explicit bucket boundaries
+const DEFAULT_BOUNDARIES: [f64; 15] = [
+    0.0, 5.0, 10.0, 25.0, 50.0, 75.0, 100.0, 250.0, 500.0, 750.0, 1000.0, 2500.0, 5000.0, 7500.0, 10000.0,
+];
+
+struct HistogramState {
+    start_time: u128,
+    bucket_counts: Vec<u64>,
+    count: u64,
+    sum: f64,
+    min: f64,
+    max: f64,
+}
+
+impl HistogramState {
+    fn new(buckets: usize) -> Self {
+        Self {
+            start_time: utilities::nanos(),
+            bucket_counts: vec![0; buckets],
+            count: 0,
+            sum: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        }
+    }
+}
+
+pub struct Histogram {
+    tracer: Arc<OtlpTracer>,
+    name: String,
+    description: String,
+    unit: String,
+    attributes: HashMap<String, AnyValue>,
+    boundaries: Vec<f64>,
+    temporality: Temporality,
+    state: SyncMutex<HistogramState>,
+}
+
+impl Histogram {
+    pub fn new(
+        tracer: Arc<OtlpTracer>,
+        name: &str,
+        description: &str,
+        unit: &str,
+    ) -> Self {
+        Self {
+            tracer,
+            name: name.to_string(),
+            description: description.to_string(),
+            unit: unit.to_string(),
+            attributes: HashMap::new(),
+            boundaries: DEFAULT_BOUNDARIES.to_vec(),
+            temporality: Temporality::Cumulative,
+            state: SyncMutex::new(HistogramState::new(DEFAULT_BOUNDARIES.len() + 1)),
+        }
+    }
+
+    pub fn with_attribute(mut self, key: impl Into<String>, value: impl Into<AnyValue>) -> Self {
+        self.attributes.insert(key.into(), value.into());
+        self
+    }
+
+    /// Sets the upper bounds of the buckets, values above the last bound land in an overflow bucket
+    pub fn with_boundaries(mut self, mut boundaries: Vec<f64>) -> Self {
+        boundaries.retain(|bound| bound.is_finite());
+        boundaries.sort_by(|a, b| a.total_cmp(b));
+        boundaries.dedup();
+        self.state = SyncMutex::new(HistogramState::new(boundaries.len() + 1));
+        self.boundaries = boundaries;
+        self
+    }
+
+    pub fn with_temporality(mut self, temporality: Temporality) -> Self {
+        self.temporality = temporality;
+        self
+    }
+
+    pub fn record(&self, value: f64) {
+        if !value.is_finite() {
+            return;
+        }
+        // Buckets are upper-bound inclusive: (bounds[i - 1], bounds[i]]
+        let index = self.boundaries.partition_point(|bound| *bound < value);
+        let mut state = self.state.lock().unwrap();
+        state.bucket_counts[index] += 1;
+        state.count += 1;
+        state.sum += value;
+        state.min = state.min.min(value);
+        state.max = state.max.max(value);
+    }
+
+    pub async fn upload(&self) -> simple_error::SimpleResult<()> {
+        let metric = self.collect();
+        self.tracer.export_metrics(vec![metric]).await
+    }
+}
+
+impl Collect for Histogram {
+    fn collect(&self) -> Metric {
+        let now = utilities::nanos();
+        let mut state = self.state.lock().unwrap();
+
+        let data_point = HistogramDataPoint {
+            attributes: Attributes::from(self.attributes.clone()).0,
+            start_time_unix_nano: state.start_time.to_string(),
+            time_unix_nano: now.to_string(),
+            count: state.count,
+            sum: state.sum,
+            bucket_counts: state.bucket_counts.clone(),
+            explicit_bounds: self.boundaries.clone(),
+            min: (state.count > 0).then_some(state.min),
+            max: (state.count > 0).then_some(state.max),
+        };
+
+        // Delta series start over after every collection
+        if self.temporality == Temporality::Delta {
+            *state = HistogramState::new(self.boundaries.len() + 1);
+            state.start_time = now;
+        }
+
+        Metric {
+            name: self.name.clone(),
+            description: self.description.clone(),
+            unit: self.unit.clone(),
+            data: MetricData::Histogram(HistogramData {
+                aggregation_temporality: self.temporality as i64,
+                data_points: vec![data_point],
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn histogram(boundaries: Vec<f64>) -> Histogram {
+        let tracer = OtlpTracer::new("http://localhost:4318/v1/traces", "http://localhost:4318/v1/metrics", "test").unwrap();
+        Histogram::new(Arc::new(tracer), "latency", "", "ms").with_boundaries(boundaries)
+    }
+
+    fn data_point(histogram: &Histogram) -> HistogramDataPoint {
+        match histogram.collect().data {
+            MetricData::Histogram(mut data) => data.data_points.remove(0),
+            _ => panic!("expected a histogram"),
+        }
+    }
+
+    #[test]
+    fn value_on_a_bound_lands_in_that_bucket() {
+        let histogram = histogram(vec![10.0, 20.0]);
+        histogram.record(10.0);
+        histogram.record(20.0);
+        histogram.record(10.5);
+
+        assert_eq!(data_point(&histogram).bucket_counts, vec![1, 2, 0]);
+    }
+
+    #[test]
+    fn values_above_the_last_bound_land_in_the_overflow_bucket() {
+        let histogram = histogram(vec![10.0, 20.0]);
+        histogram.record(-5.0);
+        histogram.record(20.5);
+        histogram.record(1000.0);
+
+        let data_point = data_point(&histogram);
+        assert_eq!(data_point.bucket_counts, vec![1, 0, 2]);
+        assert_eq!(data_point.count, 3);
+        assert_eq!(data_point.sum, 1015.5);
+        assert_eq!(data_point.min, Some(-5.0));
+        assert_eq!(data_point.max, Some(1000.0));
+    }
+
+    #[test]
+    fn non_finite_values_are_ignored() {
+        let histogram = histogram(vec![10.0]);
+        histogram.record(f64::NAN);
+        histogram.record(f64::INFINITY);
+        histogram.record(f64::NEG_INFINITY);
+
+        let data_point = data_point(&histogram);
+        assert_eq!(data_point.bucket_counts, vec![0, 0]);
+        assert_eq!(data_point.count, 0);
+        assert_eq!(data_point.sum, 0.0);
+        assert_eq!(data_point.min, None);
+        assert_eq!(data_point.max, None);
+    }
+
+    #[test]
+    fn delta_histogram_starts_over_after_collection() {
+        let histogram = histogram(vec![10.0]).with_temporality(Temporality::Delta);
+        histogram.record(5.0);
+        histogram.record(15.0);
+        let first = data_point(&histogram);
+        assert_eq!(first.bucket_counts, vec![1, 1]);
+        assert_eq!(first.count, 2);
+
+        let second = data_point(&histogram);
+        assert_eq!(second.bucket_counts, vec![0, 0]);
+        assert_eq!(second.count, 0);
+        assert_eq!(second.sum, 0.0);
+        assert_eq!(second.min, None);
+        // The next window starts where the previous one ended
+        assert_eq!(second.start_time_unix_nano, first.time_unix_nano);
+
+        histogram.record(7.0);
+        let third = data_point(&histogram);
+        assert_eq!(third.bucket_counts, vec![1, 0]);
+        assert_eq!(third.min, Some(7.0));
+    }
+
+    #[test]
+    fn cumulative_histogram_keeps_counting() {
+        let histogram = histogram(vec![10.0]);
+        histogram.record(5.0);
+        let first = data_point(&histogram);
+        histogram.record(15.0);
+        let second = data_point(&histogram);
+
+        assert_eq!(second.bucket_counts, vec![1, 1]);
+        assert_eq!(second.start_time_unix_nano, first.start_time_unix_nano);
+    }
+
+    #[test]
+    fn boundaries_are_sorted_and_deduplicated() {
+        let histogram = histogram(vec![50.0, 10.0, f64::NAN, 20.0, 10.0, f64::INFINITY]);
+        assert_eq!(histogram.boundaries, vec![10.0, 20.0, 50.0]);
+
+        histogram.record(15.0);
+        let data_point = data_point(&histogram);
+        assert_eq!(data_point.explicit_bounds, vec![10.0, 20.0, 50.0]);
+        assert_eq!(data_point.bucket_counts, vec![0, 1, 0, 0]);
+    }
+}
@@ -0,0 +1,210 @@
+use std::cell::Cell;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Weak};
+use std::task::{Context, Poll};
+
+use crate::span_context::SpanContext;
+use crate::span_guard::{self, SpanGuard, CURRENT_SPAN_CONTEXT, CURRENT_SPAN_GUARD};
+
+thread_local! {
+    static TELEMETRY_SUPPRESSED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// True while polling an export, so the exporter's own logging is not turned into more telemetry
+pub(crate) fn is_telemetry_suppressed() -> bool {
+    TELEMETRY_SUPPRESSED.with(|suppressed| suppressed.get())
+}
+
+pub(crate) struct Suppressed<F> {
+    inner: Pin<Box<F>>,
+}
+
+pub(crate) fn suppressed<F: Future>(future: F) -> Suppressed<F> {
+    Suppressed { inner: Box::pin(future) }
+}
+
+impl<F: Future> Future for Suppressed<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let previous = TELEMETRY_SUPPRESSED.with(|suppressed| suppressed.replace(true));
+        let result = self.inner.as_mut().poll(cx);
+        TELEMETRY_SUPPRESSED.with(|suppressed| suppressed.set(previous));
+        result
+    }
+}
+
+/// Future wrapper that carries its own current span across `.await` points.
+///
+/// The span context is installed on the polling thread for the duration of every poll and the
+/// previous one restored afterwards, so a task resuming on another executor thread keeps its
+/// parent and interleaved tasks on the same thread do not see each other's spans.
+pub struct Instrumented<F> {
+    inner: Pin<Box<F>>,
+    context: Option<SpanContext>,
+    guard: Option<Weak<SpanGuard>>,
+    span: Option<Arc<SpanGuard>>,
+}
+
+pub trait Instrument: Future + Sized {
+    /// Runs the future inside `span`, ending the span once the future completes or is dropped
+    fn instrument(self, span: Arc<SpanGuard>) -> Instrumented<Self> {
+        // The span now belongs to the future, not to whatever the caller runs next on this thread
+        span.exit_current();
+        Instrumented {
+            inner: Box::pin(self),
+            context: Some(span.context().clone()),
+            guard: Some(Arc::downgrade(&span)),
+            span: Some(span),
+        }
+    }
+
+    /// Runs the future inside the caller's current span, e.g. before handing it to `Executor::spawn`
+    fn with_current_context(self) -> Instrumented<Self> {
+        Instrumented {
+            inner: Box::pin(self),
+            context: CURRENT_SPAN_CONTEXT.with(|current| current.borrow().clone()),
+            guard: CURRENT_SPAN_GUARD.with(|current| current.borrow().clone()),
+            span: None,
+        }
+    }
+}
+
+impl<F: Future> Instrument for F {}
+
+impl<F: Future> Future for Instrumented<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        let (previous_context, previous_guard) = span_guard::swap_current(this.context.take(), this.guard.take());
+
+        let result = this.inner.as_mut().poll(cx);
+        if result.is_ready() {
+            // End the owned span while its context is still installed
+            drop(this.span.take());
+        }
+
+        // Keep whatever spans the future left open for the next poll
+        let (context, guard) = span_guard::swap_current(previous_context, previous_guard);
+        this.context = context;
+        this.guard = guard;
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use smol::Executor;
+    use smol::future::{self, yield_now};
+
+    use super::*;
+    use crate::structs::{Span, SpanKind, Status, StatusCode};
+    use crate::tracer::OtlpTracer;
+
+    struct Fixture {
+        executor: Arc<Executor<'static>>,
+        tracer: Arc<OtlpTracer>,
+    }
+
+    impl Fixture {
+        fn new() -> Self {
+            let tracer = OtlpTracer::new("http://localhost:4318/v1/traces", "http://localhost:4318/v1/metrics", "test").unwrap();
+            Self { executor: Arc::new(Executor::new()), tracer: Arc::new(tracer) }
+        }
+
+        fn start(&self, name: &str) -> Arc<SpanGuard> {
+            let status = Status { message: String::new(), code: StatusCode::Unset as i64 };
+            SpanGuard::start(&self.executor, &self.tracer, name, status, SpanKind::Internal, HashMap::new(), None)
+        }
+
+        fn run<T>(&self, future: impl Future<Output = T>) -> T {
+            smol::block_on(self.executor.run(future))
+        }
+
+        /// Parent span id of every ended span, by name
+        fn parents(&self) -> HashMap<String, String> {
+            self.tracer.take_queued_spans()
+                .into_iter()
+                .map(|span: Span| (span.name, span.parent_span_id))
+                .collect()
+        }
+    }
+
+    fn current_span_id() -> Option<String> {
+        CURRENT_SPAN_CONTEXT.with(|current| current.borrow().as_ref().map(|context| context.span_id.clone()))
+    }
+
+    #[test]
+    fn interleaved_futures_keep_their_own_parent() {
+        let fixture = Fixture::new();
+        let child = |name: &'static str, parent_id: String| {
+            let fixture = &fixture;
+            async move {
+                yield_now().await;
+                assert_eq!(current_span_id(), Some(parent_id));
+                let child = fixture.start(name);
+                yield_now().await;
+                drop(child);
+            }
+        };
+
+        let a = fixture.start("a");
+        let a_id = a.context().span_id.clone();
+        let a_future = child("a.child", a_id.clone()).instrument(a);
+        let b = fixture.start("b");
+        let b_id = b.context().span_id.clone();
+        let b_future = child("b.child", b_id.clone()).instrument(b);
+
+        fixture.run(future::zip(a_future, b_future));
+
+        let parents = fixture.parents();
+        assert_eq!(parents["a.child"], a_id);
+        assert_eq!(parents["b.child"], b_id);
+        // `instrument` detached `a` from the thread, so `b` did not become its child
+        assert_eq!(parents["b"], "");
+    }
+
+    #[test]
+    fn child_held_across_await_restores_its_parent() {
+        let fixture = Fixture::new();
+        let parent = fixture.start("parent");
+        let parent_id = parent.context().span_id.clone();
+
+        fixture.run(async {
+            let child = fixture.start("child");
+            yield_now().await;
+            drop(child);
+            assert_eq!(current_span_id().as_ref(), Some(&parent_id));
+            drop(fixture.start("sibling"));
+        }.instrument(parent));
+
+        let parents = fixture.parents();
+        assert_eq!(parents["child"], parent_id);
+        assert_eq!(parents["sibling"], parent_id);
+        assert_eq!(current_span_id(), None);
+    }
+
+    #[test]
+    fn instrument_ends_span_on_completion_or_drop() {
+        let fixture = Fixture::new();
+
+        let completed = fixture.start("completed");
+        // The span belongs to the future, the caller's thread no longer sees it as current
+        let future = async { 1 }.instrument(completed);
+        assert_eq!(current_span_id(), None);
+        assert_eq!(fixture.run(future), 1);
+        assert!(fixture.parents().contains_key("completed"));
+
+        let dropped = fixture.start("dropped");
+        let mut future = future::pending::<()>().instrument(dropped);
+        assert!(fixture.run(future::poll_once(&mut future)).is_none());
+        assert!(fixture.parents().is_empty());
+        drop(future);
+        assert!(fixture.parents().contains_key("dropped"));
+    }
+}
@@ -7,11 +7,36 @@ mod utilities;
 mod metric_base;
 mod gauge;
 mod counter;
+mod histogram;
+mod up_down_counter;
+mod number;
+mod meter_provider;
+mod batch_processor;
+mod protobuf;
+mod instrument;
+mod log_filter;
+mod log_format;
+mod env_config;
+mod resource;
+mod retry;
+mod response;
+mod connection_pool;
 pub mod globals;
 pub mod logger;
+pub mod propagation;
 
-pub use tracer::OtlpTracer;
+pub use tracer::{Compression, ExportConfig, OtlpTracer, Protocol};
 pub use span_guard::SpanGuard;
+pub use span_context::SpanContext;
+pub use instrument::{Instrument, Instrumented};
 pub use structs::*;
 pub use gauge::Gauge;
 pub use counter::Counter;
+pub use histogram::Histogram;
+pub use up_down_counter::UpDownCounter;
+pub use number::Number;
+pub use meter_provider::{Collect, MeterProvider};
+pub use batch_processor::{BatchConfig, FlushReport};
+pub use resource::ResourceDetector;
+pub use retry::{ExportError, RetryConfig};
+pub use response::PartialSuccess;
@@ -0,0 +1,357 @@
+use std::env;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+
+use log::{LevelFilter, Metadata, Record};
+
+#[derive(Clone, Debug)]
+struct LogDirective {
+    module: Option<String>,
+    level: LevelFilter,
+}
+
+impl LogDirective {
+    /// A module matches itself and its children, `foo` matches `foo::bar` but not `foobar`
+    fn matches(&self, target: &str) -> bool {
+        match &self.module {
+            Some(module) => target
+                .strip_prefix(module.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with("::")),
+            None => true,
+        }
+    }
+}
+
+#[cfg(not(feature = "regex"))]
+const REGEX_METACHARACTERS: &[char] = &['.', '*', '+', '?', '(', ')', '[', ']', '{', '}', '|', '^', '$', '\\'];
+
+/// Message filter after the `/` in a spec, a regex with the `regex` feature and a substring otherwise
+#[derive(Clone, Debug)]
+struct MessageFilter {
+    #[cfg(feature = "regex")]
+    inner: regex::Regex,
+    #[cfg(not(feature = "regex"))]
+    inner: String,
+}
+
+impl MessageFilter {
+    fn new(spec: &str) -> Option<Self> {
+        #[cfg(feature = "regex")]
+        let inner = match regex::Regex::new(spec) {
+            Ok(regex) => regex,
+            Err(e) => {
+                eprintln!("Ignoring invalid log message filter {:?}: {}", spec, e);
+                return None;
+            }
+        };
+        #[cfg(not(feature = "regex"))]
+        let inner = {
+            if spec.contains(REGEX_METACHARACTERS) {
+                eprintln!(
+                    "Log message filter {:?} is matched as a plain substring, enable the `regex` feature for regex filters",
+                    spec,
+                );
+            }
+            spec.to_string()
+        };
+        Some(Self { inner })
+    }
+
+    fn is_match(&self, message: &str) -> bool {
+        #[cfg(feature = "regex")]
+        {
+            self.inner.is_match(message)
+        }
+        #[cfg(not(feature = "regex"))]
+        {
+            message.contains(self.inner.as_str())
+        }
+    }
+}
+
+/// `RUST_LOG` style filter with env_logger semantics
+#[derive(Clone, Debug)]
+pub(crate) struct LogFilter {
+    /// Sorted by module length so the longest match is found last
+    directives: Vec<LogDirective>,
+    message: Option<MessageFilter>,
+}
+
+impl LogFilter {
+    /// Parses `RUST_LOG`, defaulting to `info` when it is unset or empty
+    pub fn from_env() -> Self {
+        match env::var("RUST_LOG") {
+            Ok(spec) if !spec.trim().is_empty() => Self::parse(&spec),
+            _ => Self::parse("info"),
+        }
+    }
+
+    /// Parses a comma separated list of `module=level`, `module` or `level` directives with an optional `/filter` suffix
+    pub fn parse(spec: &str) -> Self {
+        let mut parts = spec.splitn(2, '/');
+        let directives_spec = parts.next().unwrap_or_default();
+        let message = parts.next().and_then(|filter| {
+            if filter.contains('/') {
+                eprintln!("Ignoring log message filter {:?}: only one '/' is allowed", filter);
+                return None;
+            }
+            MessageFilter::new(filter)
+        });
+
+        let mut directives = Vec::new();
+        for directive in directives_spec.split(',').map(str::trim) {
+            if directive.is_empty() {
+                continue;
+            }
+
+            let parts: Vec<&str> = directive.split('=').collect();
+            let parsed = match parts.as_slice() {
+                // A bare level applies globally, anything else is a module enabled at every level
+                [part] => match LevelFilter::from_str(part) {
+                    Ok(level) => Some(LogDirective { module: None, level }),
+                    Err(_) => Some(LogDirective { module: Some(part.to_string()), level: LevelFilter::Trace }),
+                },
+                [module, level] => LevelFilter::from_str(level.trim()).ok().map(|level| LogDirective {
+                    module: Some(module.trim().to_string()).filter(|module| !module.is_empty()),
+                    level,
+                }),
+                _ => None,
+            };
+
+            match parsed {
+                Some(parsed) => directives.push(parsed),
+                None => eprintln!("Ignoring invalid log directive {:?}", directive),
+            }
+        }
+
+        // Like env_logger, a spec without usable directives still shows errors
+        if directives.is_empty() {
+            directives.push(LogDirective { module: None, level: LevelFilter::Error });
+        }
+
+        // Stable sort, so a later duplicate still wins
+        directives.sort_by_key(|directive| directive.module.as_ref().map_or(0, String::len));
+        Self { directives, message }
+    }
+
+    /// Level of the longest matching directive, records without a matching directive are disabled
+    fn level_for(&self, target: &str) -> LevelFilter {
+        self.directives.iter()
+            .rev()
+            .find(|directive| directive.matches(target))
+            .map_or(LevelFilter::Off, |directive| directive.level)
+    }
+
+    pub fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level_for(metadata.target())
+    }
+
+    /// Applies the message filter on top of [`LogFilter::enabled`]
+    pub fn matches(&self, record: &Record) -> bool {
+        if !self.enabled(record.metadata()) {
+            return false;
+        }
+        match &self.message {
+            Some(message) => message.is_match(&record.args().to_string()),
+            None => true,
+        }
+    }
+
+    /// Most verbose level any directive allows, suitable for `log::set_max_level`
+    pub fn max_level(&self) -> LevelFilter {
+        self.directives.iter()
+            .map(|directive| directive.level)
+            .max()
+            .unwrap_or(LevelFilter::Off)
+    }
+}
+
+/// Destination of a log record, each with its own filter
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogSink {
+    /// Lines printed to stdout, filtered by `RUST_LOG`
+    Console,
+    /// Events attached to the current span
+    SpanEvents,
+    /// OTLP log records
+    Export,
+}
+
+/// Filters for every sink, span events and exports fall back to the console filter when unset
+#[derive(Clone, Debug)]
+pub(crate) struct LogFilters {
+    console: LogFilter,
+    span_events: Option<LogFilter>,
+    export: Option<LogFilter>,
+}
+
+impl LogFilters {
+    pub fn new(console: LogFilter, span_events: Option<LogFilter>, export: Option<LogFilter>) -> Self {
+        Self { console, span_events, export }
+    }
+
+    pub fn get(&self, sink: LogSink) -> &LogFilter {
+        match sink {
+            LogSink::Console => &self.console,
+            LogSink::SpanEvents => self.span_events.as_ref().unwrap_or(&self.console),
+            LogSink::Export => self.export.as_ref().unwrap_or(&self.console),
+        }
+    }
+
+    fn set(&mut self, sink: LogSink, filter: LogFilter) {
+        match sink {
+            LogSink::Console => self.console = filter,
+            LogSink::SpanEvents => self.span_events = Some(filter),
+            LogSink::Export => self.export = Some(filter),
+        }
+    }
+
+    fn all(&self) -> impl Iterator<Item = &LogFilter> {
+        std::iter::once(&self.console)
+            .chain(self.span_events.as_ref())
+            .chain(self.export.as_ref())
+    }
+
+    /// True if any sink accepts records with this metadata
+    pub fn enabled(&self, metadata: &Metadata) -> bool {
+        self.all().any(|filter| filter.enabled(metadata))
+    }
+
+    /// Most verbose level across all sinks
+    pub fn max_level(&self) -> LevelFilter {
+        self.all().map(LogFilter::max_level).max().unwrap_or(LevelFilter::Off)
+    }
+}
+
+/// Handle returned by `logger::init` to swap the active filters while the program runs
+#[derive(Clone, Debug)]
+pub struct LogFilterHandle {
+    filters: Arc<RwLock<LogFilters>>,
+}
+
+impl LogFilterHandle {
+    pub(crate) fn new(filters: Arc<RwLock<LogFilters>>) -> Self {
+        Self { filters }
+    }
+
+    /// Replaces every console directive with those in `spec`, using the same syntax as `RUST_LOG`
+    pub fn reload(&self, spec: &str) {
+        self.reload_sink(LogSink::Console, spec);
+    }
+
+    /// Re-reads `RUST_LOG` for the console
+    pub fn reload_from_env(&self) {
+        self.replace(LogSink::Console, LogFilter::from_env());
+    }
+
+    /// Replaces the directives of one sink, a span event or export filter stops following the console once set
+    pub fn reload_sink(&self, sink: LogSink, spec: &str) {
+        self.replace(sink, LogFilter::parse(spec));
+    }
+
+    fn replace(&self, sink: LogSink, filter: LogFilter) {
+        // Hold the lock while updating the max level so concurrent reloads cannot interleave
+        let mut filters = self.filters.write().unwrap();
+        filters.set(sink, filter);
+        log::set_max_level(filters.max_level());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use log::Level;
+
+    use super::*;
+
+    fn enabled(filter: &LogFilter, target: &str, level: Level) -> bool {
+        filter.enabled(&Metadata::builder().target(target).level(level).build())
+    }
+
+    fn matches(filter: &LogFilter, target: &str, level: Level, message: &str) -> bool {
+        filter.matches(&Record::builder().target(target).level(level).args(format_args!("{}", message)).build())
+    }
+
+    #[test]
+    fn longest_module_prefix_wins() {
+        for spec in ["foo=warn,foo::bar=trace", "foo::bar=trace,foo=warn"] {
+            let filter = LogFilter::parse(spec);
+            assert!(enabled(&filter, "foo::bar::baz", Level::Trace), "{}", spec);
+            assert!(enabled(&filter, "foo::qux", Level::Warn), "{}", spec);
+            assert!(!enabled(&filter, "foo::qux", Level::Info), "{}", spec);
+        }
+    }
+
+    #[test]
+    fn module_matches_only_at_path_boundaries() {
+        let filter = LogFilter::parse("foo=debug");
+        assert!(enabled(&filter, "foo", Level::Debug));
+        assert!(enabled(&filter, "foo::bar", Level::Debug));
+        assert!(!enabled(&filter, "foobar", Level::Error));
+    }
+
+    #[test]
+    fn bare_module_enables_every_level() {
+        let filter = LogFilter::parse("warn,foo");
+        assert!(enabled(&filter, "foo::bar", Level::Trace));
+        assert!(!enabled(&filter, "other", Level::Info));
+    }
+
+    #[test]
+    fn off_disables_a_module() {
+        let filter = LogFilter::parse("info,noisy=off");
+        assert!(!enabled(&filter, "noisy", Level::Error));
+        assert!(enabled(&filter, "quiet", Level::Info));
+        assert!(!enabled(&LogFilter::parse("off"), "quiet", Level::Error));
+    }
+
+    #[test]
+    fn later_duplicate_wins() {
+        assert!(enabled(&LogFilter::parse("foo=error,foo=debug"), "foo", Level::Debug));
+        assert!(!enabled(&LogFilter::parse("foo=debug,foo=error"), "foo", Level::Warn));
+        assert!(enabled(&LogFilter::parse("info,debug"), "any", Level::Debug));
+    }
+
+    #[test]
+    fn invalid_spec_falls_back_to_error() {
+        for spec in ["", "foo=loud", "a=b=c", ",,"] {
+            let filter = LogFilter::parse(spec);
+            assert!(enabled(&filter, "any", Level::Error), "{:?}", spec);
+            assert!(!enabled(&filter, "any", Level::Warn), "{:?}", spec);
+        }
+        // Valid directives survive next to invalid ones
+        assert!(enabled(&LogFilter::parse("foo=loud,info"), "foo", Level::Info));
+    }
+
+    #[test]
+    fn message_filter_applies_after_the_level() {
+        let filter = LogFilter::parse("info/hello");
+        assert!(matches(&filter, "any", Level::Info, "say hello world"));
+        assert!(!matches(&filter, "any", Level::Info, "goodbye"));
+        assert!(!matches(&filter, "any", Level::Debug, "hello"));
+        assert!(LogFilter::parse("info/a/b").message.is_none());
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn message_filter_is_a_regex_with_the_feature() {
+        let filter = LogFilter::parse("info/^fo+$");
+        assert!(matches(&filter, "any", Level::Info, "foooo"));
+        assert!(!matches(&filter, "any", Level::Info, "a foo"));
+    }
+
+    #[cfg(not(feature = "regex"))]
+    #[test]
+    fn message_filter_is_a_substring_without_the_feature() {
+        let filter = LogFilter::parse("info/fo+");
+        assert!(matches(&filter, "any", Level::Info, "a fo+ b"));
+        assert!(!matches(&filter, "any", Level::Info, "foo"));
+    }
+
+    #[test]
+    fn max_level_is_the_most_verbose_directive() {
+        assert_eq!(LogFilter::parse("warn,foo=debug").max_level(), LevelFilter::Debug);
+        assert_eq!(LogFilter::parse("foo").max_level(), LevelFilter::Trace);
+        assert_eq!(LogFilter::parse("off").max_level(), LevelFilter::Off);
+        assert_eq!(LogFilter::parse("").max_level(), LevelFilter::Error);
+    }
+}
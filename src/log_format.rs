@@ -0,0 +1,188 @@
+use std::borrow::Cow;
+use std::fmt::Write as _;
+use std::str::FromStr;
+
+use miniserde::json::{self, Number, Object};
+use miniserde::{ser, Serialize};
+use simple_error::SimpleError;
+
+use crate::structs::{AnyValue, Attribute};
+
+/// Console output format for `SpanLogger`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// One JSON object per line
+    #[default]
+    Json,
+    /// Human-readable single line, optionally colored
+    Compact,
+    /// `key=value` pairs
+    Logfmt,
+}
+
+impl FromStr for LogFormat {
+    type Err = SimpleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "json" => Ok(LogFormat::Json),
+            "compact" => Ok(LogFormat::Compact),
+            "logfmt" => Ok(LogFormat::Logfmt),
+            other => Err(SimpleError::new(format!("unknown log format: {}", other))),
+        }
+    }
+}
+
+/// Everything a formatter may print for one record
+pub(crate) struct LogLine<'a> {
+    pub timestamp: String,
+    pub level: log::Level,
+    pub module: &'a str,
+    pub message: String,
+    pub fields: &'a [Attribute],
+    /// `(trace_id, span_id)` of the current span
+    pub trace: Option<(&'a str, &'a str)>,
+    /// `(file, line)` of the call site
+    pub location: Option<(&'a str, u32)>,
+    pub thread: Option<String>,
+}
+
+impl LogLine<'_> {
+    pub fn format(&self, format: LogFormat, colors: bool) -> String {
+        match format {
+            LogFormat::Json => self.json(),
+            LogFormat::Compact => self.compact(colors),
+            LogFormat::Logfmt => self.logfmt(),
+        }
+    }
+
+    fn json(&self) -> String {
+        let mut fields = Object::new();
+        for attribute in self.fields {
+            fields.insert(attribute.key.clone(), json_value(&attribute.value));
+        }
+
+        let mut object = vec![
+            ("timestamp", json::Value::String(self.timestamp.clone())),
+            ("level", json::Value::String(self.level.to_string().to_lowercase())),
+            ("module", json::Value::String(self.module.to_string())),
+            ("message", json::Value::String(self.message.clone())),
+            ("fields", json::Value::Object(fields)),
+        ];
+        if let Some((trace_id, span_id)) = self.trace {
+            object.push(("trace_id", json::Value::String(trace_id.to_string())));
+            object.push(("span_id", json::Value::String(span_id.to_string())));
+        }
+        if let Some((file, line)) = self.location {
+            object.push(("file", json::Value::String(file.to_string())));
+            object.push(("line", json::Value::Number(Number::U64(line as u64))));
+        }
+        if let Some(thread) = &self.thread {
+            object.push(("thread", json::Value::String(thread.clone())));
+        }
+        json::to_string(&OrderedObject(object))
+    }
+
+    fn compact(&self, colors: bool) -> String {
+        let level = format!("{:<5}", self.level);
+        let mut line = if colors {
+            let color = match self.level {
+                log::Level::Error => "31",
+                log::Level::Warn => "33",
+                log::Level::Info => "32",
+                log::Level::Debug => "34",
+                log::Level::Trace => "35",
+            };
+            format!("{} \x1b[{}m{}\x1b[0m \x1b[2m{}:\x1b[0m {}", self.timestamp, color, level, self.module, self.message)
+        } else {
+            format!("{} {} {}: {}", self.timestamp, level, self.module, self.message)
+        };
+
+        for attribute in self.fields {
+            let _ = write!(line, " {}={}", attribute.key, display_value(&attribute.value));
+        }
+        if let Some((trace_id, span_id)) = self.trace {
+            let _ = write!(line, " trace_id={} span_id={}", trace_id, span_id);
+        }
+        if let Some((file, number)) = self.location {
+            let _ = write!(line, " ({}:{})", file, number);
+        }
+        if let Some(thread) = &self.thread {
+            let _ = write!(line, " [{}]", thread);
+        }
+        line
+    }
+
+    fn logfmt(&self) -> String {
+        let mut line = format!(
+            "ts={} level={} module={} msg={}",
+            self.timestamp,
+            self.level.to_string().to_lowercase(),
+            logfmt_value(self.module),
+            logfmt_value(&self.message),
+        );
+
+        for attribute in self.fields {
+            let _ = write!(line, " {}={}", attribute.key, logfmt_value(&display_value(&attribute.value)));
+        }
+        if let Some((trace_id, span_id)) = self.trace {
+            let _ = write!(line, " trace_id={} span_id={}", trace_id, span_id);
+        }
+        if let Some((file, number)) = self.location {
+            let _ = write!(line, " file={} line={}", logfmt_value(file), number);
+        }
+        if let Some(thread) = &self.thread {
+            let _ = write!(line, " thread={}", logfmt_value(thread));
+        }
+        line
+    }
+}
+
+/// JSON object that keeps insertion order, unlike `json::Object`
+struct OrderedObject(Vec<(&'static str, json::Value)>);
+
+struct OrderedObjectStream<'a> {
+    entries: std::slice::Iter<'a, (&'static str, json::Value)>,
+}
+
+impl ser::Map for OrderedObjectStream<'_> {
+    fn next(&mut self) -> Option<(Cow<str>, &dyn Serialize)> {
+        self.entries.next().map(|(key, value)| (Cow::Borrowed(*key), value as &dyn Serialize))
+    }
+}
+
+impl Serialize for OrderedObject {
+    fn begin(&self) -> ser::Fragment {
+        ser::Fragment::Map(Box::new(OrderedObjectStream { entries: self.0.iter() }))
+    }
+}
+
+/// Plain JSON for console output, non-scalar values fall back to their OTLP JSON form
+fn json_value(value: &AnyValue) -> json::Value {
+    match value {
+        AnyValue::String(value) => json::Value::String(value.clone()),
+        AnyValue::Bool(value) => json::Value::Bool(*value),
+        AnyValue::Int(value) => json::Value::Number(Number::I64(*value)),
+        AnyValue::Double(value) => json::Value::Number(Number::F64(*value)),
+        other => json::Value::String(json::to_string(other)),
+    }
+}
+
+fn display_value(value: &AnyValue) -> String {
+    match value {
+        AnyValue::String(value) => value.clone(),
+        AnyValue::Bool(value) => value.to_string(),
+        AnyValue::Int(value) => value.to_string(),
+        AnyValue::Double(value) => value.to_string(),
+        other => json::to_string(other),
+    }
+}
+
+/// Quotes values containing spaces, quotes or `=` so lines stay parseable
+fn logfmt_value(value: &str) -> String {
+    if value.is_empty() || value.contains([' ', '"', '=', '\n', '\t']) {
+        format!("{:?}", value)
+    } else {
+        value.to_string()
+    }
+}
@@ -1,125 +1,278 @@
 use std::env;
+use std::io::IsTerminal;
 use std::str::FromStr;
+use std::sync::{Arc, RwLock};
 
+use log::kv::{self, Key, Value, VisitSource};
 use log::{Log, Metadata, Record};
-use miniserde::Serialize;
 use simple_error::{box_err, SimpleResult};
 
-use crate::span_guard::CURRENT_SPAN_GUARD;
+use crate::globals;
+use crate::instrument;
+use crate::log_filter::{LogFilter, LogFilters};
+use crate::log_format::LogLine;
+use crate::span_guard::{CURRENT_SPAN_CONTEXT, CURRENT_SPAN_GUARD};
+use crate::structs::{AnyValue, Attribute, LogRecord};
 use crate::utilities;
 
-#[derive(Serialize)]
-struct LogMessage {
-    timestamp: String,
-    level: String,
-    module: String,
-    message: String,
-}
+pub use crate::log_filter::{LogFilterHandle, LogSink};
+pub use crate::log_format::LogFormat;
+
+/// Environment variable read by [`LoggerConfig::from_env`], e.g. `compact,trace_ids,location,thread`
+pub const LOG_FORMAT_ENV: &str = "SMOL_OTEL_LOG_FORMAT";
+
+/// `RUST_LOG` style directives for span events, read by [`LoggerConfig::from_env`]
+pub const SPAN_EVENTS_LOG_ENV: &str = "SMOL_OTEL_SPAN_EVENTS_LOG";
+
+/// `RUST_LOG` style directives for exported log records, read by [`LoggerConfig::from_env`]
+pub const EXPORT_LOG_ENV: &str = "SMOL_OTEL_EXPORT_LOG";
 
+/// Console output and per-sink filter options for `SpanLogger`
 #[derive(Clone, Debug)]
-struct LogDirective {
-    module: Option<String>,
-    level: log::LevelFilter,
+pub struct LoggerConfig {
+    format: LogFormat,
+    colors: bool,
+    trace_ids: bool,
+    location: bool,
+    thread: bool,
+    span_event_filter: Option<String>,
+    export_filter: Option<String>,
 }
 
-pub struct SpanLogger {
-    directives: Vec<LogDirective>,
+impl Default for LoggerConfig {
+    fn default() -> Self {
+        Self {
+            format: LogFormat::default(),
+            colors: std::io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none(),
+            trace_ids: false,
+            location: false,
+            thread: false,
+            span_event_filter: None,
+            export_filter: None,
+        }
+    }
 }
 
-impl SpanLogger {
-    fn new() -> Self {
-        let directives = Self::parse_env_directives();
-        Self { directives }
-    }
-
-    fn parse_env_directives() -> Vec<LogDirective> {
-        let mut directives = Vec::new();
-        
-        // Get RUST_LOG environment variable
-        if let Ok(env_filter) = env::var("RUST_LOG") {
-            for directive in env_filter.split(',') {
-                if directive.is_empty() {
-                    continue;
-                }
-                
-                // Parse module=level or just level
-                let parts: Vec<&str> = directive.split('=').collect();
-                match parts.as_slice() {
-                    [module, level] => {
-                        if let Ok(level) = log::LevelFilter::from_str(level) {
-                            directives.push(LogDirective {
-                                module: Some(module.to_string()),
-                                level,
-                            });
-                        }
-                    }
-                    [level] => {
-                        if let Ok(level) = log::LevelFilter::from_str(level) {
-                            directives.push(LogDirective {
-                                module: None,
-                                level,
-                            });
-                        }
-                    }
-                    _ => continue,
-                }
+impl LoggerConfig {
+    /// Reads a format name followed by optional comma separated flags from `SMOL_OTEL_LOG_FORMAT`,
+    /// and the span event and export filters from `SMOL_OTEL_SPAN_EVENTS_LOG` and `SMOL_OTEL_EXPORT_LOG`
+    pub fn from_env() -> Self {
+        let mut config = Self {
+            span_event_filter: env::var(SPAN_EVENTS_LOG_ENV).ok(),
+            export_filter: env::var(EXPORT_LOG_ENV).ok(),
+            ..Self::default()
+        };
+
+        let Ok(value) = env::var(LOG_FORMAT_ENV) else {
+            return config;
+        };
+
+        for option in value.split(',').map(str::trim) {
+            match option {
+                "trace_ids" => config.trace_ids = true,
+                "location" => config.location = true,
+                "thread" => config.thread = true,
+                "no_colors" => config.colors = false,
+                _ => match LogFormat::from_str(option) {
+                    Ok(format) => config.format = format,
+                    Err(e) => eprintln!("Ignoring {} option: {}", LOG_FORMAT_ENV, e),
+                },
             }
         }
-        
-        // Only default to Info if no RUST_LOG was set
-        if directives.is_empty() {
-            directives.push(LogDirective {
-                module: None,
-                level: log::LevelFilter::Info,
-            });
-        }
-        
-        directives
+        config
+    }
+
+    pub fn with_format(mut self, format: LogFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Colors the level in the compact format, defaults to on when stdout is a terminal and `NO_COLOR` is unset
+    pub fn with_colors(mut self, colors: bool) -> Self {
+        self.colors = colors;
+        self
+    }
+
+    /// Adds the current span's trace and span ids to every line
+    pub fn with_trace_ids(mut self, trace_ids: bool) -> Self {
+        self.trace_ids = trace_ids;
+        self
+    }
+
+    /// Adds the call site's file and line to every line
+    pub fn with_location(mut self, location: bool) -> Self {
+        self.location = location;
+        self
+    }
+
+    /// Adds the thread name to every line
+    pub fn with_thread(mut self, thread: bool) -> Self {
+        self.thread = thread;
+        self
+    }
+
+    /// Directives deciding which records become span events, follows `RUST_LOG` when unset
+    pub fn with_span_event_filter(mut self, spec: &str) -> Self {
+        self.span_event_filter = Some(spec.to_string());
+        self
+    }
+
+    /// Directives deciding which records are exported over OTLP, follows `RUST_LOG` when unset
+    pub fn with_export_filter(mut self, spec: &str) -> Self {
+        self.export_filter = Some(spec.to_string());
+        self
     }
 }
 
-impl Log for SpanLogger {
-    fn enabled(&self, metadata: &Metadata) -> bool {        
-        // First check module-specific directives
-        for directive in &self.directives {
-            match &directive.module {
-                Some(module) => {
-                    if metadata.target().starts_with(module) {
-                        return metadata.level() <= directive.level;
-                    }
-                }
-                None => continue  // Skip global directive for now
-            }
+/// Collects a record's structured key-values as attributes
+#[derive(Default)]
+struct KeyValues(Vec<Attribute>);
+
+impl<'kvs> VisitSource<'kvs> for KeyValues {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+        let value = if let Some(value) = value.to_bool() {
+            AnyValue::Bool(value)
+        } else if let Some(value) = value.to_i64() {
+            AnyValue::Int(value)
+        } else if let Some(value) = value.to_f64() {
+            AnyValue::Double(value)
+        } else {
+            AnyValue::String(value.to_string())
+        };
+        self.0.push(Attribute::new(key.as_str(), value));
+        Ok(())
+    }
+}
+
+impl KeyValues {
+    fn from_record(record: &Record) -> Self {
+        let mut key_values = Self::default();
+        // Visiting only fails if the visitor does, which ours never does
+        let _ = record.key_values().visit(&mut key_values);
+        key_values
+    }
+}
+
+pub struct SpanLogger {
+    filters: Arc<RwLock<LogFilters>>,
+    config: LoggerConfig,
+}
+
+impl SpanLogger {
+    fn new(config: LoggerConfig) -> Self {
+        let filters = LogFilters::new(
+            LogFilter::from_env(),
+            config.span_event_filter.as_deref().map(LogFilter::parse),
+            config.export_filter.as_deref().map(LogFilter::parse),
+        );
+        Self { filters: Arc::new(RwLock::new(filters)), config }
+    }
+
+    /// Builds an OTLP log record correlated with the current span, if any
+    fn log_record(record: &Record, fields: &[Attribute]) -> LogRecord {
+        let now = utilities::nanos().to_string();
+        let severity_number = match record.level() {
+            log::Level::Trace => 1,
+            log::Level::Debug => 5,
+            log::Level::Info => 9,
+            log::Level::Warn => 13,
+            log::Level::Error => 17,
+        };
+
+        let mut attributes = vec![Attribute::new("log.target", record.target())];
+        if let Some(module_path) = record.module_path() {
+            attributes.push(Attribute::new("code.namespace", module_path));
+        }
+        if let Some(file) = record.file() {
+            attributes.push(Attribute::new("code.filepath", file));
+        }
+        if let Some(line) = record.line() {
+            attributes.push(Attribute::new("code.lineno", line));
         }
+        attributes.extend(fields.iter().cloned());
 
-        // If no module matches, fall back to global directive
-        for directive in &self.directives {
-            if directive.module.is_none() {
-                return metadata.level() <= directive.level;
-            }
+        let context = CURRENT_SPAN_CONTEXT.with(|current| current.borrow().clone());
+        let (trace_id, span_id, flags) = match context {
+            Some(context) => (context.trace_id, context.span_id, context.trace_flags as i64),
+            None => (String::new(), String::new(), 0),
+        };
+
+        LogRecord {
+            time_unix_nano: now.clone(),
+            observed_time_unix_nano: now,
+            severity_number,
+            severity_text: record.level().to_string(),
+            body: AnyValue::from(record.args().to_string()),
+            attributes,
+            dropped_attributes_count: 0,
+            flags,
+            trace_id,
+            span_id,
         }
+    }
+}
 
-        false
+impl Log for SpanLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.filters.read().unwrap().enabled(metadata)
     }
 
     #[track_caller]
     fn log(&self, record: &Record) {
-        if self.enabled(record.metadata()) {
-            // Print to console
-            let log_message = LogMessage {
+        let (console, span_events, export) = {
+            let filters = self.filters.read().unwrap();
+            (
+                filters.get(LogSink::Console).matches(record),
+                filters.get(LogSink::SpanEvents).matches(record),
+                filters.get(LogSink::Export).matches(record),
+            )
+        };
+        if !(console || span_events || export) {
+            return;
+        }
+
+        let fields = KeyValues::from_record(record);
+
+        // Print to console
+        if console {
+            let context = CURRENT_SPAN_CONTEXT.with(|current| current.borrow().clone());
+            let thread = std::thread::current();
+            let log_line = LogLine {
                 timestamp: utilities::iso_timestamp(),
-                level: record.level().to_string().to_lowercase(),
-                module: record.target().to_string(),
+                level: record.level(),
+                module: record.target(),
                 message: record.args().to_string(),
+                fields: &fields.0,
+                trace: context.as_ref()
+                    .filter(|_| self.config.trace_ids)
+                    .map(|context| (context.trace_id.as_str(), context.span_id.as_str())),
+                location: record.file()
+                    .zip(record.line())
+                    .filter(|_| self.config.location),
+                thread: self.config.thread
+                    .then(|| thread.name().map(str::to_string).unwrap_or_else(|| format!("{:?}", thread.id()))),
             };
-            println!("{}", miniserde::json::to_string(&log_message));
-
-            // push log to current span
-            CURRENT_SPAN_GUARD.with(|current| {
-                if let Some(guard) = current.borrow().as_ref() {
-                    guard.push_event(record.level(), record.args());
-                }
-            });
+            println!("{}", log_line.format(self.config.format, self.config.colors));
+        }
+
+        // Logging from inside an export only goes to the console, otherwise exports would feed themselves
+        if instrument::is_telemetry_suppressed() {
+            return;
+        }
+
+        // push log to current span
+        if span_events {
+            let guard = CURRENT_SPAN_GUARD.with(|current| current.borrow().as_ref().and_then(|guard| guard.upgrade()));
+            if let Some(guard) = guard {
+                guard.push_event(record.level(), record.args(), &fields.0);
+            }
+        }
+
+        // export log record
+        if export {
+            if let (Some(executor), Some(tracer)) = (globals::try_executor(), globals::try_tracer()) {
+                tracer.on_log(executor, Self::log_record(record, &fields.0));
+            }
         }
     }
 
@@ -128,9 +281,20 @@ impl Log for SpanLogger {
     }
 }
 
-pub fn init() -> SimpleResult<()> {
-    let logger = SpanLogger::new();
+/// Installs `SpanLogger` with console options from [`LoggerConfig::from_env`]
+pub fn init() -> SimpleResult<LogFilterHandle> {
+    init_with(LoggerConfig::from_env())
+}
+
+/// Installs `SpanLogger`, the returned handle changes the `RUST_LOG` directives at runtime
+pub fn init_with(config: LoggerConfig) -> SimpleResult<LogFilterHandle> {
+    let logger = SpanLogger::new(config);
+    let handle = LogFilterHandle::new(logger.filters.clone());
+    let max_level = logger.filters.read().unwrap().max_level();
     log::set_logger(Box::leak(Box::new(logger)))
-        .map(|()| log::set_max_level(log::LevelFilter::Trace))
+        .map(|()| {
+            log::set_max_level(max_level);
+            handle
+        })
         .map_err(|e| box_err!(format!("failed to set logger: {}", e)))
 }
@@ -0,0 +1,111 @@
+use std::sync::Arc;
+use std::sync::Mutex as SyncMutex;
+use std::time::{Duration, Instant};
+
+use smol::{Executor, Task, Timer};
+
+use crate::batch_processor::{self, FlushReport};
+use crate::instrument;
+use crate::structs::Metric;
+use crate::tracer::OtlpTracer;
+
+/// Anything that can report its current state as an OTLP metric
+pub trait Collect {
+    fn collect(&self) -> Metric;
+}
+
+/// Periodically collects every registered instrument and exports them together in one request
+pub struct MeterProvider {
+    tracer: Arc<OtlpTracer>,
+    interval: Duration,
+    instruments: SyncMutex<Vec<Arc<dyn Collect + Send + Sync>>>,
+    reader: SyncMutex<Option<Task<()>>>,
+}
+
+impl MeterProvider {
+    pub fn new(tracer: Arc<OtlpTracer>, interval: Duration) -> Self {
+        Self {
+            tracer,
+            interval,
+            instruments: SyncMutex::new(Vec::new()),
+            reader: SyncMutex::new(None),
+        }
+    }
+
+    pub fn register<I: Collect + Send + Sync + 'static>(&self, instrument: &Arc<I>) {
+        self.instruments.lock().unwrap().push(instrument.clone());
+    }
+
+    /// Spawns the periodic reader on `executor`, calling it again is a no-op. Once started, the provider is also
+    /// flushed by [`OtlpTracer::force_flush`] and stopped by [`OtlpTracer::shutdown`].
+    pub fn start(self: &Arc<Self>, executor: &Arc<Executor<'static>>) {
+        let mut reader = self.reader.lock().unwrap();
+        if reader.is_some() {
+            return;
+        }
+        self.tracer.register_meter_provider(self);
+
+        let provider = self.clone();
+        let executor_clone = executor.clone();
+        *reader = Some(executor.spawn(async move {
+            loop {
+                Timer::after(provider.interval).await;
+                let metrics = provider.collect();
+                if metrics.is_empty() {
+                    continue;
+                }
+                let tracer = provider.tracer.clone();
+                provider.tracer.spawn_export(&executor_clone, "metrics", metrics.len(), async move {
+                    tracer.export_metrics(metrics).await
+                });
+            }
+        }));
+    }
+
+    fn collect(&self) -> Vec<Metric> {
+        self.instruments.lock().unwrap()
+            .iter()
+            .map(|instrument| instrument.collect())
+            .collect()
+    }
+
+    /// Collects all registered instruments and exports them right away
+    pub async fn force_flush(&self) -> FlushReport {
+        self.flush_until(Instant::now() + self.tracer.metrics_config.timeout).await
+    }
+
+    pub(crate) async fn flush_until(&self, deadline: Instant) -> FlushReport {
+        let metrics = self.collect();
+        let mut report = FlushReport::default();
+        if metrics.is_empty() {
+            return report;
+        }
+
+        let items = metrics.len();
+        let export = instrument::suppressed(self.tracer.export_metrics(metrics));
+        let success = match batch_processor::with_deadline(deadline, export).await {
+            Some(Ok(())) => true,
+            Some(Err(e)) => {
+                eprintln!("Failed to upload metrics: {}", e);
+                false
+            }
+            None => false,
+        };
+        report.record(items, success);
+        report
+    }
+
+    /// Stops the periodic reader
+    pub(crate) async fn stop(&self) {
+        let reader = self.reader.lock().unwrap().take();
+        if let Some(reader) = reader {
+            reader.cancel().await;
+        }
+    }
+
+    /// Stops the periodic reader and performs a final collection
+    pub async fn shutdown(&self) -> FlushReport {
+        self.stop().await;
+        self.force_flush().await
+    }
+}
@@ -1,21 +1,66 @@
-use std::sync::Arc;
-use std::sync::atomic::{AtomicI64, Ordering};
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 
+use crate::number::{AtomicNumber, Number};
 use crate::structs::*;
 use crate::utilities;
 use crate::tracer::OtlpTracer;
 
-pub(crate) struct MetricBase {
+/// Default maximum number of distinct attribute sets per instrument, including the overflow series
+const DEFAULT_CARDINALITY_LIMIT: usize = 2000;
+
+const OVERFLOW_KEY: &str = "otel.metric.overflow";
+
+/// Aggregated value for one distinct attribute set
+struct Series<T: Number> {
+    attributes: Vec<Attribute>,
+    value: AtomicNumber<T>,
+    /// Set by every measurement, so the pre-created series are only exported once used
+    recorded: AtomicBool,
+}
+
+impl<T: Number> Series<T> {
+    fn new(attributes: Vec<Attribute>) -> Arc<Self> {
+        Arc::new(Self {
+            attributes,
+            value: AtomicNumber::new(T::ZERO),
+            recorded: AtomicBool::new(false),
+        })
+    }
+
+    fn set(&self, value: T) {
+        self.value.store(value);
+        self.recorded.store(true, Ordering::Relaxed);
+    }
+
+    fn add(&self, value: T) {
+        self.value.fetch_add(value);
+        self.recorded.store(true, Ordering::Relaxed);
+    }
+}
+
+pub(crate) struct MetricBase<T: Number> {
     tracer: Arc<OtlpTracer>,
     name: String,
     description: String,
     unit: String,
-    value: Arc<AtomicI64>,
-    attributes: HashMap<String, String>,
+    attributes: HashMap<String, AnyValue>,
+    /// Series with at least one attribute, by [`attributes_hash`]
+    series: RwLock<HashMap<u64, Vec<Arc<Series<T>>>>>,
+    /// Measurements without attributes skip the lookup entirely
+    empty: Arc<Series<T>>,
+    overflow: Arc<Series<T>>,
+    cardinality_limit: usize,
+    temporality: Temporality,
+    /// Creation time for cumulative series, time of the last collection for delta series
+    start_time: AtomicU64,
 }
 
-impl MetricBase {
+impl<T: Number> MetricBase<T> {
     pub fn new(
         tracer: Arc<OtlpTracer>,
         name: &str,
@@ -27,71 +72,310 @@ impl MetricBase {
             name: name.to_string(),
             description: description.to_string(),
             unit: unit.to_string(),
-            value: Arc::new(AtomicI64::new(0)),
             attributes: HashMap::new(),
+            series: RwLock::new(HashMap::new()),
+            empty: Series::new(vec![]),
+            overflow: Series::new(vec![Attribute::new(OVERFLOW_KEY, true)]),
+            cardinality_limit: DEFAULT_CARDINALITY_LIMIT,
+            temporality: Temporality::Cumulative,
+            start_time: AtomicU64::new(utilities::nanos() as u64),
         }
     }
 
-    pub fn with_attribute(&mut self, key: impl Into<String>, value: impl Into<String>) {
+    pub fn with_attribute(&mut self, key: impl Into<String>, value: impl Into<AnyValue>) {
         self.attributes.insert(key.into(), value.into());
     }
 
-    pub fn get_value(&self) -> i64 {
-        self.value.load(Ordering::SeqCst)
+    pub fn with_cardinality_limit(&mut self, limit: usize) {
+        self.cardinality_limit = limit.max(1);
     }
 
-    pub fn set_value(&self, value: i64) {
-        self.value.store(value, Ordering::SeqCst);
+    pub fn with_temporality(&mut self, temporality: Temporality) {
+        self.temporality = temporality;
     }
 
-    pub fn add_value(&self, value: i64) {
-        self.value.fetch_add(value, Ordering::SeqCst);
+    fn find(series: &HashMap<u64, Vec<Arc<Series<T>>>>, hash: u64, attributes: &[Attribute]) -> Option<Arc<Series<T>>> {
+        series.get(&hash)?
+            .iter()
+            .find(|series| same_attributes(&series.attributes, attributes))
+            .cloned()
     }
 
-    pub async fn upload_metric(&self, start_time: String, is_monotonic: bool, aggregation_temporality: i64) -> simple_error::SimpleResult<()> {
-        let data_point = DataPoint {
-            attributes: Attributes::from(self.attributes.clone()).0,
-            start_time_unix_nano: start_time,
-            time_unix_nano: utilities::nanos().to_string(),
-            as_double: self.get_value(),
-        };
+    /// Finds or creates the series for `attributes`, falling back to the overflow series past the cardinality limit
+    fn series(&self, attributes: &[Attribute]) -> Arc<Series<T>> {
+        if attributes.is_empty() {
+            return self.empty.clone();
+        }
+        let attributes = deduplicated(attributes);
+        let hash = attributes_hash(&attributes);
+        if let Some(existing) = Self::find(&self.series.read().unwrap(), hash, &attributes) {
+            return existing;
+        }
+
+        let mut series = self.series.write().unwrap();
+        // Another thread may have created it between releasing the read lock and taking the write lock
+        if let Some(existing) = Self::find(&series, hash, &attributes) {
+            return existing;
+        }
+        // The empty and overflow series take one slot each
+        let len: usize = series.values().map(Vec::len).sum();
+        if len + 2 >= self.cardinality_limit {
+            return self.overflow.clone();
+        }
+        let created = Series::new(attributes.into_owned());
+        series.entry(hash).or_default().push(created.clone());
+        created
+    }
 
-        let metric = Metric {
+    pub fn set_value(&self, value: T, attributes: &[Attribute]) {
+        self.series(attributes).set(value);
+    }
+
+    pub fn add_value(&self, value: T, attributes: &[Attribute]) {
+        self.series(attributes).add(value);
+    }
+
+    /// Every series with a measurement, the pre-created ones only once used
+    fn all_series(&self) -> Vec<Arc<Series<T>>> {
+        let series = self.series.read().unwrap();
+        [&self.empty, &self.overflow].into_iter()
+            .filter(|series| series.recorded.load(Ordering::Relaxed))
+            .chain(series.values().flatten())
+            .cloned()
+            .collect()
+    }
+
+    fn data_point(&self, series: &Series<T>, start_time: u64, time: u64, value: T) -> DataPoint {
+        // Attributes passed with the measurement override constant ones with the same key
+        let mut attributes: Vec<Attribute> = Attributes::from(self.attributes.clone()).0
+            .into_iter()
+            .filter(|constant| !series.attributes.iter().any(|attribute| attribute.key == constant.key))
+            .collect();
+        attributes.extend(series.attributes.iter().cloned());
+        DataPoint {
+            attributes,
+            start_time_unix_nano: start_time.to_string(),
+            time_unix_nano: time.to_string(),
+            value: value.into_value(),
+        }
+    }
+
+    fn data_points(&self, start_time: u64, time: u64, read: impl Fn(&AtomicNumber<T>) -> T) -> Vec<DataPoint> {
+        self.all_series().iter()
+            .map(|series| self.data_point(series, start_time, time, read(&series.value)))
+            .collect()
+    }
+
+    /// Reads and resets the series measured since the previous collection and forgets the others, freeing their cardinality slots
+    fn delta_data_points(&self, start_time: u64, time: u64) -> Vec<DataPoint> {
+        let mut measured: Vec<Arc<Series<T>>> = [&self.empty, &self.overflow].into_iter()
+            .filter(|series| series.recorded.swap(false, Ordering::Relaxed))
+            .cloned()
+            .collect();
+        self.series.write().unwrap().retain(|_, bucket| {
+            bucket.retain(|series| {
+                let recorded = series.recorded.swap(false, Ordering::Relaxed);
+                if recorded {
+                    measured.push(series.clone());
+                }
+                recorded
+            });
+            !bucket.is_empty()
+        });
+
+        measured.iter()
+            .map(|series| self.data_point(series, start_time, time, series.value.swap(T::ZERO)))
+            .collect()
+    }
+
+    fn metric(&self, data: MetricData) -> Metric {
+        Metric {
             name: self.name.clone(),
             description: self.description.clone(),
             unit: self.unit.clone(),
-            sum: Sum {
-                aggregation_temporality,
-                is_monotonic,
-                data_points: vec![data_point],
-            },
-        };
+            data,
+        }
+    }
 
-        let scope_metrics = ScopeMetrics {
-            scope: Scope {
-                name: env!("CARGO_PKG_NAME").to_string(),
-                version: env!("CARGO_PKG_VERSION").to_string(),
-                attributes: vec![],
-                dropped_attributes_count: 0,
-            },
-            metrics: vec![metric],
+    pub fn sum_metric(&self, is_monotonic: bool) -> Metric {
+        let now = utilities::nanos() as u64;
+        let data_points = match self.temporality {
+            // Cumulative series keep a stable start time so backends can compute rates
+            Temporality::Cumulative => self.data_points(self.start_time.load(Ordering::SeqCst), now, |value| value.load()),
+            // Delta series report what happened since the previous collection and start over
+            Temporality::Delta => {
+                let start_time = self.start_time.swap(now, Ordering::SeqCst);
+                self.delta_data_points(start_time, now)
+            }
         };
 
-        let resource_metrics = ResourceMetrics {
-            resource: Resource {
-                attributes: {
-                    let mut map = HashMap::new();
-                    map.insert("service.name".to_string(), self.tracer.service_name.clone());
-                    map.insert("telemetry.sdk.language".to_string(), "rust".to_string());
-                    map.insert("telemetry.sdk.name".to_string(), "opentelemetry".to_string());
-                    map.insert("telemetry.sdk.version".to_string(), "1.0.0".to_string());
-                    Attributes::from(map).0
-                },
-                dropped_attributes_count: 0,
-            },
-            scope_metrics: vec![scope_metrics],
-        };
+        self.metric(MetricData::Sum(Sum {
+            aggregation_temporality: self.temporality as i64,
+            is_monotonic,
+            data_points,
+        }))
+    }
+
+    pub fn gauge_metric(&self) -> Metric {
+        let now = utilities::nanos() as u64;
+        self.metric(MetricData::Gauge(GaugeData {
+            data_points: self.data_points(now, now, |value| value.load()),
+        }))
+    }
+
+    pub async fn upload(&self, metric: Metric) -> simple_error::SimpleResult<()> {
+        self.tracer.export_metrics(vec![metric]).await
+    }
+}
+
+/// Sorted by key with the last duplicate winning, only allocating when there are duplicates
+fn deduplicated(attributes: &[Attribute]) -> Cow<'_, [Attribute]> {
+    let has_duplicates = attributes.iter()
+        .enumerate()
+        .any(|(i, attribute)| attributes[i + 1..].iter().any(|other| other.key == attribute.key));
+    if !has_duplicates {
+        return Cow::Borrowed(attributes);
+    }
+    Cow::Owned(
+        attributes.iter()
+            .map(|attribute| (attribute.key.clone(), attribute.value.clone()))
+            .collect::<BTreeMap<_, _>>()
+            .into_iter()
+            .map(|(key, value)| Attribute { key, value })
+            .collect()
+    )
+}
+
+/// Sum of the per-attribute hashes, so equal sets hash the same in any order
+fn attributes_hash(attributes: &[Attribute]) -> u64 {
+    attributes.iter().fold(0u64, |sum, attribute| {
+        let mut hasher = DefaultHasher::new();
+        attribute.key.hash(&mut hasher);
+        hash_value(&attribute.value, &mut hasher);
+        sum.wrapping_add(hasher.finish())
+    })
+}
+
+fn hash_value(value: &AnyValue, hasher: &mut impl Hasher) {
+    std::mem::discriminant(value).hash(hasher);
+    match value {
+        AnyValue::String(value) => value.hash(hasher),
+        AnyValue::Bool(value) => value.hash(hasher),
+        AnyValue::Int(value) => value.hash(hasher),
+        AnyValue::Double(value) => value.to_bits().hash(hasher),
+        AnyValue::Bytes(value) => value.hash(hasher),
+        AnyValue::Array(values) => values.iter().for_each(|value| hash_value(value, hasher)),
+        AnyValue::KvList(attributes) => attributes.iter().for_each(|attribute| {
+            attribute.key.hash(hasher);
+            hash_value(&attribute.value, hasher);
+        }),
+    }
+}
+
+/// Both sides are free of duplicate keys, so equal length and inclusion mean the same set
+fn same_attributes(a: &[Attribute], b: &[Attribute]) -> bool {
+    a.len() == b.len() && b.iter().all(|attribute| a.contains(attribute))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn measurement_attributes_override_constant_ones() {
+        let tracer = OtlpTracer::new("http://localhost:4318/v1/traces", "http://localhost:4318/v1/metrics", "test").unwrap();
+        let mut base = MetricBase::<i64>::new(Arc::new(tracer), "requests", "", "1");
+        base.with_attribute("deployment.environment", "prod");
+        base.with_attribute("host.name", "web-1");
+        base.add_value(1, &[Attribute::new("deployment.environment", "staging"), Attribute::new("route", "/a")]);
 
-        self.tracer.upload_metrics(vec![resource_metrics]).await
+        let data_points = base.data_points(0, 1, |value| value.load());
+        let mut attributes: Vec<(String, AnyValue)> = data_points[0].attributes.iter()
+            .map(|attribute| (attribute.key.clone(), attribute.value.clone()))
+            .collect();
+        attributes.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(attributes, vec![
+            ("deployment.environment".to_string(), AnyValue::from("staging")),
+            ("host.name".to_string(), AnyValue::from("web-1")),
+            ("route".to_string(), AnyValue::from("/a")),
+        ]);
     }
-} 
\ No newline at end of file
+
+    fn base(cardinality_limit: usize) -> MetricBase<i64> {
+        let tracer = OtlpTracer::new("http://localhost:4318/v1/traces", "http://localhost:4318/v1/metrics", "test").unwrap();
+        let mut base = MetricBase::new(Arc::new(tracer), "requests", "", "1");
+        base.with_cardinality_limit(cardinality_limit);
+        base
+    }
+
+    #[test]
+    fn equal_attribute_sets_share_a_series() {
+        let base = base(DEFAULT_CARDINALITY_LIMIT);
+        base.add_value(1, &[Attribute::new("a", 1i64), Attribute::new("b", "x")]);
+        base.add_value(1, &[Attribute::new("b", "x"), Attribute::new("a", 1i64)]);
+        // The last duplicate wins
+        base.add_value(1, &[Attribute::new("a", 0i64), Attribute::new("b", "x"), Attribute::new("a", 1i64)]);
+        base.add_value(1, &[Attribute::new("a", 1.0), Attribute::new("b", "x")]);
+
+        let mut values: Vec<NumberValue> = base.data_points(0, 1, |value| value.load()).into_iter().map(|point| point.value).collect();
+        values.sort_by_key(|value| match value {
+            NumberValue::Int(value) => *value,
+            NumberValue::Double(_) => 0,
+        });
+        assert_eq!(values, vec![NumberValue::Int(1), NumberValue::Int(3)]);
+    }
+
+    #[test]
+    fn empty_attribute_set_is_exported_once_used() {
+        let base = base(DEFAULT_CARDINALITY_LIMIT);
+        assert!(base.data_points(0, 1, |value| value.load()).is_empty());
+
+        base.add_value(2, &[]);
+        base.add_value(3, &[]);
+        let data_points = base.data_points(0, 1, |value| value.load());
+        assert_eq!(data_points.len(), 1);
+        assert!(data_points[0].attributes.is_empty());
+        assert_eq!(data_points[0].value, NumberValue::Int(5));
+    }
+
+    #[test]
+    fn sets_past_the_cardinality_limit_go_to_overflow() {
+        // Two regular series, the empty one and the overflow one
+        let base = base(4);
+        for i in 0..5i64 {
+            base.add_value(1, &[Attribute::new("id", i)]);
+        }
+
+        let data_points = base.data_points(0, 1, |value| value.load());
+        assert_eq!(data_points.len(), 3);
+        let overflow = data_points.iter()
+            .find(|point| point.attributes.iter().any(|attribute| attribute.key == OVERFLOW_KEY))
+            .unwrap();
+        assert_eq!(overflow.value, NumberValue::Int(3));
+    }
+
+    #[test]
+    fn delta_collection_forgets_idle_series() {
+        let mut base = base(4);
+        base.with_temporality(Temporality::Delta);
+        base.add_value(1, &[Attribute::new("id", 0i64)]);
+        base.add_value(1, &[Attribute::new("id", 1i64)]);
+        base.add_value(1, &[Attribute::new("id", 2i64)]);
+        assert_eq!(base.delta_data_points(0, 1).len(), 3);
+
+        // Only the series measured since the last collection are reported and kept
+        base.add_value(2, &[Attribute::new("id", 0i64)]);
+        let data_points = base.delta_data_points(1, 2);
+        assert_eq!(data_points.len(), 1);
+        assert_eq!(data_points[0].attributes, vec![Attribute::new("id", 0i64)]);
+        assert_eq!(data_points[0].value, NumberValue::Int(2));
+        assert!(base.delta_data_points(2, 3).is_empty());
+
+        // The freed slots take new attribute sets instead of the overflow series
+        base.add_value(1, &[Attribute::new("id", 3i64)]);
+        base.add_value(1, &[Attribute::new("id", 4i64)]);
+        let data_points = base.delta_data_points(3, 4);
+        assert_eq!(data_points.len(), 2);
+        assert!(data_points.iter().all(|point| point.attributes[0].key == "id"));
+    }
+}
@@ -0,0 +1,91 @@
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::structs::NumberValue;
+
+/// Value types an instrument can record, exported as `asInt` or `asDouble` data points
+pub trait Number: Copy + PartialOrd + Send + Sync + std::ops::Neg<Output = Self> + 'static {
+    const ZERO: Self;
+    const ONE: Self;
+
+    fn to_atomic(self) -> u64;
+    fn from_atomic(bits: u64) -> Self;
+    fn add(self, other: Self) -> Self;
+    fn into_value(self) -> NumberValue;
+}
+
+impl Number for i64 {
+    const ZERO: Self = 0;
+    const ONE: Self = 1;
+
+    fn to_atomic(self) -> u64 {
+        self as u64
+    }
+
+    fn from_atomic(bits: u64) -> Self {
+        bits as i64
+    }
+
+    fn add(self, other: Self) -> Self {
+        self.wrapping_add(other)
+    }
+
+    fn into_value(self) -> NumberValue {
+        NumberValue::Int(self)
+    }
+}
+
+impl Number for f64 {
+    const ZERO: Self = 0.0;
+    const ONE: Self = 1.0;
+
+    fn to_atomic(self) -> u64 {
+        self.to_bits()
+    }
+
+    fn from_atomic(bits: u64) -> Self {
+        f64::from_bits(bits)
+    }
+
+    fn add(self, other: Self) -> Self {
+        self + other
+    }
+
+    fn into_value(self) -> NumberValue {
+        NumberValue::Double(self)
+    }
+}
+
+/// Lock-free cell for any [`Number`], stored as its raw 64 bits
+pub(crate) struct AtomicNumber<T: Number> {
+    bits: AtomicU64,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Number> AtomicNumber<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            bits: AtomicU64::new(value.to_atomic()),
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn load(&self) -> T {
+        T::from_atomic(self.bits.load(Ordering::SeqCst))
+    }
+
+    pub fn store(&self, value: T) {
+        self.bits.store(value.to_atomic(), Ordering::SeqCst);
+    }
+
+    pub fn swap(&self, value: T) -> T {
+        T::from_atomic(self.bits.swap(value.to_atomic(), Ordering::SeqCst))
+    }
+
+    pub fn fetch_add(&self, value: T) {
+        // compare-and-swap loop, since there is no native atomic add for floats
+        let _ = self.bits.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |bits| {
+            Some(T::from_atomic(bits).add(value).to_atomic())
+        });
+    }
+}
@@ -0,0 +1,205 @@
+use http::{HeaderMap, HeaderValue};
+
+use crate::span_context::SpanContext;
+use crate::span_guard::CURRENT_SPAN_CONTEXT;
+
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+pub const TRACESTATE_HEADER: &str = "tracestate";
+
+const SUPPORTED_VERSION: u8 = 0;
+const MAX_TRACESTATE_MEMBERS: usize = 32;
+
+/// Writes `traceparent` and `tracestate` headers for `context`
+pub fn inject(context: &SpanContext, headers: &mut HeaderMap) {
+    let traceparent = format!(
+        "{:02x}-{}-{}-{:02x}",
+        SUPPORTED_VERSION, context.trace_id, context.span_id, context.trace_flags
+    );
+    if let Ok(value) = HeaderValue::from_str(&traceparent) {
+        headers.insert(TRACEPARENT_HEADER, value);
+    }
+
+    if !context.trace_state.is_empty() {
+        if let Ok(value) = HeaderValue::from_str(&context.trace_state) {
+            headers.insert(TRACESTATE_HEADER, value);
+        }
+    }
+}
+
+/// Injects the current span of this thread, if there is one
+pub fn inject_current(headers: &mut HeaderMap) {
+    CURRENT_SPAN_CONTEXT.with(|current| {
+        if let Some(context) = current.borrow().as_ref() {
+            inject(context, headers);
+        }
+    });
+}
+
+/// Reads a remote span context from `traceparent` and `tracestate`, ignoring malformed headers
+pub fn extract(headers: &HeaderMap) -> Option<SpanContext> {
+    let traceparent = headers.get(TRACEPARENT_HEADER)?.to_str().ok()?;
+    let (trace_id, span_id, trace_flags) = parse_traceparent(traceparent.trim())?;
+
+    // Multiple tracestate headers are combined as a single comma separated list
+    let trace_state = headers.get_all(TRACESTATE_HEADER)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .collect::<Vec<_>>()
+        .join(",");
+
+    Some(SpanContext {
+        trace_id,
+        span_id,
+        trace_flags,
+        trace_state: parse_tracestate(&trace_state),
+        is_remote: true,
+    })
+}
+
+fn is_lower_hex(value: &str, len: usize) -> bool {
+    value.len() == len && value.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+}
+
+fn parse_traceparent(value: &str) -> Option<(String, String, u8)> {
+    let parts: Vec<&str> = value.split('-').collect();
+    if parts.len() < 4 || !is_lower_hex(parts[0], 2) {
+        return None;
+    }
+
+    let version = u8::from_str_radix(parts[0], 16).ok()?;
+    // Version ff is forbidden, version 00 has exactly four fields, later versions may append more
+    if version == 0xff || (version == SUPPORTED_VERSION && parts.len() != 4) {
+        return None;
+    }
+
+    let (trace_id, span_id, flags) = (parts[1], parts[2], parts[3]);
+    if !is_lower_hex(trace_id, 32) || !is_lower_hex(span_id, 16) || !is_lower_hex(flags, 2) {
+        return None;
+    }
+    if trace_id.bytes().all(|b| b == b'0') || span_id.bytes().all(|b| b == b'0') {
+        return None;
+    }
+
+    let trace_flags = u8::from_str_radix(flags, 16).ok()?;
+    Some((trace_id.to_string(), span_id.to_string(), trace_flags))
+}
+
+/// Drops empty and malformed list members, giving up on the whole header past 32 members as the spec requires
+fn parse_tracestate(value: &str) -> String {
+    let members: Vec<&str> = value.split(',')
+        .map(|member| member.trim())
+        .filter(|member| !member.is_empty())
+        .filter(|member| member.split_once('=').is_some_and(|(key, value)| !key.is_empty() && !value.is_empty()))
+        .collect();
+
+    if members.len() > MAX_TRACESTATE_MEMBERS {
+        return String::new();
+    }
+    members.join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+    const SPAN_ID: &str = "00f067aa0ba902b7";
+
+    fn headers(traceparent: &str, tracestates: &[&str]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(TRACEPARENT_HEADER, HeaderValue::from_str(traceparent).unwrap());
+        for tracestate in tracestates {
+            headers.append(TRACESTATE_HEADER, HeaderValue::from_str(tracestate).unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn inject_then_extract_round_trips() {
+        let context = SpanContext {
+            trace_id: TRACE_ID.to_string(),
+            span_id: SPAN_ID.to_string(),
+            trace_flags: 0x01,
+            trace_state: "vendor=value,other=1".to_string(),
+            is_remote: false,
+        };
+        let mut headers = HeaderMap::new();
+        inject(&context, &mut headers);
+        assert_eq!(headers[TRACEPARENT_HEADER], format!("00-{}-{}-01", TRACE_ID, SPAN_ID).as_str());
+
+        let extracted = extract(&headers).unwrap();
+        assert_eq!(extracted, SpanContext { is_remote: true, ..context });
+    }
+
+    #[test]
+    fn inject_skips_empty_tracestate() {
+        let context = SpanContext {
+            trace_id: TRACE_ID.to_string(),
+            span_id: SPAN_ID.to_string(),
+            trace_flags: 0x00,
+            trace_state: String::new(),
+            is_remote: false,
+        };
+        let mut headers = HeaderMap::new();
+        inject(&context, &mut headers);
+        assert_eq!(headers[TRACEPARENT_HEADER], format!("00-{}-{}-00", TRACE_ID, SPAN_ID).as_str());
+        assert!(!headers.contains_key(TRACESTATE_HEADER));
+    }
+
+    #[test]
+    fn extracts_unsampled_context() {
+        let context = extract(&headers(&format!("00-{}-{}-00", TRACE_ID, SPAN_ID), &[])).unwrap();
+        assert!(!context.is_sampled());
+        assert!(context.is_remote);
+        assert_eq!(context.trace_state, "");
+    }
+
+    #[test]
+    fn rejects_invalid_traceparent() {
+        let zeros_trace = "0".repeat(32);
+        let zeros_span = "0".repeat(16);
+        for traceparent in [
+            format!("ff-{}-{}-01", TRACE_ID, SPAN_ID),
+            format!("00-{}-{}-01", zeros_trace, SPAN_ID),
+            format!("00-{}-{}-01", TRACE_ID, zeros_span),
+            format!("00-{}-{}-01", TRACE_ID.to_uppercase(), SPAN_ID),
+            format!("00-{}-{}-0A", TRACE_ID, SPAN_ID),
+            format!("0A-{}-{}-01", TRACE_ID, SPAN_ID),
+            format!("00-{}-{}-01-extra", TRACE_ID, SPAN_ID),
+            format!("00-{}-{}", TRACE_ID, SPAN_ID),
+            format!("00-{}-{}-01", &TRACE_ID[1..], SPAN_ID),
+            format!("00-{}-{}0-01", TRACE_ID, SPAN_ID),
+            format!("00-{}-{}-zz", TRACE_ID, SPAN_ID),
+            "".to_string(),
+        ] {
+            assert_eq!(parse_traceparent(&traceparent), None, "{}", traceparent);
+        }
+        assert_eq!(extract(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn accepts_future_versions_with_extra_fields() {
+        let parsed = parse_traceparent(&format!("cc-{}-{}-01-what-the-future-holds", TRACE_ID, SPAN_ID));
+        assert_eq!(parsed, Some((TRACE_ID.to_string(), SPAN_ID.to_string(), 0x01)));
+        // Extra fields are only allowed past version 00, the fields it defines must still be valid
+        assert_eq!(parse_traceparent(&format!("cc-{}-{}-1", TRACE_ID, SPAN_ID)), None);
+    }
+
+    #[test]
+    fn combines_multiple_tracestate_headers() {
+        let headers = headers(&format!("00-{}-{}-01", TRACE_ID, SPAN_ID), &["a=1,b=2", "c=3"]);
+        assert_eq!(extract(&headers).unwrap().trace_state, "a=1,b=2,c=3");
+    }
+
+    #[test]
+    fn drops_malformed_tracestate_members() {
+        assert_eq!(parse_tracestate(" a=1 ,, =2,b=,c, d=4"), "a=1,d=4");
+    }
+
+    #[test]
+    fn discards_tracestate_past_32_members() {
+        let members = |count: usize| (0..count).map(|i| format!("k{}=v", i)).collect::<Vec<_>>().join(",");
+        assert_eq!(parse_tracestate(&members(32)), members(32));
+        assert_eq!(parse_tracestate(&members(33)), "");
+    }
+}
@@ -0,0 +1,621 @@
+//! Minimal hand-rolled protobuf encoder for the OTLP export requests.
+//!
+//! Field numbers follow `opentelemetry/proto/{trace,metrics,common,resource}/v1/*.proto`.
+//! Encoding omits proto3 default values like a generated encoder would. Decoding only covers the
+//! `partial_success` field of the export responses.
+
+use crate::structs::*;
+
+const WIRE_VARINT: u8 = 0;
+const WIRE_FIXED64: u8 = 1;
+const WIRE_LENGTH_DELIMITED: u8 = 2;
+const WIRE_FIXED32: u8 = 5;
+
+pub(crate) trait ProtoEncode {
+    fn encode(&self, encoder: &mut ProtoEncoder);
+
+    fn encode_to_vec(&self) -> Vec<u8> {
+        let mut encoder = ProtoEncoder::default();
+        self.encode(&mut encoder);
+        encoder.buf
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct ProtoEncoder {
+    buf: Vec<u8>,
+}
+
+impl ProtoEncoder {
+    fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.buf.push((value as u8) | 0x80);
+            value >>= 7;
+        }
+        self.buf.push(value as u8);
+    }
+
+    fn tag(&mut self, field: u32, wire_type: u8) {
+        self.varint(((field as u64) << 3) | wire_type as u64);
+    }
+
+    fn varint_field(&mut self, field: u32, value: u64) {
+        self.tag(field, WIRE_VARINT);
+        self.varint(value);
+    }
+
+    fn fixed64_field(&mut self, field: u32, value: [u8; 8]) {
+        self.tag(field, WIRE_FIXED64);
+        self.buf.extend_from_slice(&value);
+    }
+
+    fn bytes_field(&mut self, field: u32, value: &[u8]) {
+        self.tag(field, WIRE_LENGTH_DELIMITED);
+        self.varint(value.len() as u64);
+        self.buf.extend_from_slice(value);
+    }
+
+    pub fn uint64(&mut self, field: u32, value: u64) {
+        if value != 0 {
+            self.varint_field(field, value);
+        }
+    }
+
+    pub fn int64(&mut self, field: u32, value: i64) {
+        self.uint64(field, value as u64);
+    }
+
+    pub fn bool(&mut self, field: u32, value: bool) {
+        self.uint64(field, value as u64);
+    }
+
+    pub fn fixed64(&mut self, field: u32, value: u64) {
+        if value != 0 {
+            self.fixed64_field(field, value.to_le_bytes());
+        }
+    }
+
+    pub fn fixed32(&mut self, field: u32, value: u32) {
+        if value != 0 {
+            self.tag(field, WIRE_FIXED32);
+            self.buf.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    pub fn bytes(&mut self, field: u32, value: &[u8]) {
+        if !value.is_empty() {
+            self.bytes_field(field, value);
+        }
+    }
+
+    /// Fields declared `optional` keep explicit presence, so zero is still written
+    pub fn optional_double(&mut self, field: u32, value: f64) {
+        self.fixed64_field(field, value.to_le_bytes());
+    }
+
+    pub fn packed_fixed64(&mut self, field: u32, values: &[u64]) {
+        let packed: Vec<u8> = values.iter().flat_map(|value| value.to_le_bytes()).collect();
+        self.bytes(field, &packed);
+    }
+
+    pub fn packed_double(&mut self, field: u32, values: &[f64]) {
+        let packed: Vec<u8> = values.iter().flat_map(|value| value.to_le_bytes()).collect();
+        self.bytes(field, &packed);
+    }
+
+    pub fn string(&mut self, field: u32, value: &str) {
+        self.bytes(field, value.as_bytes());
+    }
+
+    /// Hex-encoded ids are carried as raw bytes on the wire
+    pub fn hex_bytes(&mut self, field: u32, value: &str) {
+        self.bytes(field, &hex::decode(value).unwrap_or_default());
+    }
+
+    /// Nanosecond timestamps are kept as decimal strings in the JSON structs
+    pub fn fixed64_str(&mut self, field: u32, value: &str) {
+        self.fixed64(field, value.parse().unwrap_or(0));
+    }
+
+    /// Embedded messages are always written, even when empty, so presence is preserved
+    pub fn message<T: ProtoEncode + ?Sized>(&mut self, field: u32, value: &T) {
+        let nested = value.encode_to_vec();
+        self.bytes_field(field, &nested);
+    }
+
+    pub fn messages<T: ProtoEncode>(&mut self, field: u32, values: &[T]) {
+        for value in values {
+            self.message(field, value);
+        }
+    }
+}
+
+impl ProtoEncode for ResourceSpansRoot {
+    fn encode(&self, encoder: &mut ProtoEncoder) {
+        encoder.messages(1, &self.resource_spans);
+    }
+}
+
+impl ProtoEncode for ResourceSpan {
+    fn encode(&self, encoder: &mut ProtoEncoder) {
+        encoder.message(1, &self.resource);
+        encoder.messages(2, &self.scope_spans);
+    }
+}
+
+impl ProtoEncode for Resource {
+    fn encode(&self, encoder: &mut ProtoEncoder) {
+        encoder.messages(1, &self.attributes);
+        encoder.int64(2, self.dropped_attributes_count);
+    }
+}
+
+impl ProtoEncode for Attribute {
+    fn encode(&self, encoder: &mut ProtoEncoder) {
+        encoder.string(1, &self.key);
+        encoder.message(2, &self.value);
+    }
+}
+
+// AnyValue is a oneof, so the selected field is written even when it holds the default value
+impl ProtoEncode for AnyValue {
+    fn encode(&self, encoder: &mut ProtoEncoder) {
+        match self {
+            AnyValue::String(value) => encoder.bytes_field(1, value.as_bytes()),
+            AnyValue::Bool(value) => encoder.varint_field(2, *value as u64),
+            AnyValue::Int(value) => encoder.varint_field(3, *value as u64),
+            AnyValue::Double(value) => encoder.fixed64_field(4, value.to_le_bytes()),
+            AnyValue::Array(values) => encoder.message(5, &ValueList(values)),
+            AnyValue::KvList(values) => encoder.message(6, &ValueList(values)),
+            AnyValue::Bytes(value) => encoder.bytes_field(7, value),
+        }
+    }
+}
+
+/// `ArrayValue` and `KeyValueList` both carry their entries in field 1
+struct ValueList<'a, T>(&'a [T]);
+
+impl<T: ProtoEncode> ProtoEncode for ValueList<'_, T> {
+    fn encode(&self, encoder: &mut ProtoEncoder) {
+        encoder.messages(1, self.0);
+    }
+}
+
+impl ProtoEncode for ScopeSpan {
+    fn encode(&self, encoder: &mut ProtoEncoder) {
+        encoder.message(1, &self.scope);
+        encoder.messages(2, &self.spans);
+    }
+}
+
+impl ProtoEncode for Scope {
+    fn encode(&self, encoder: &mut ProtoEncoder) {
+        encoder.string(1, &self.name);
+        encoder.string(2, &self.version);
+        encoder.messages(3, &self.attributes);
+        encoder.int64(4, self.dropped_attributes_count);
+    }
+}
+
+impl ProtoEncode for Span {
+    fn encode(&self, encoder: &mut ProtoEncoder) {
+        encoder.hex_bytes(1, &self.trace_id);
+        encoder.hex_bytes(2, &self.span_id);
+        encoder.string(3, &self.trace_state);
+        encoder.hex_bytes(4, &self.parent_span_id);
+        encoder.string(5, &self.name);
+        encoder.int64(6, self.kind);
+        encoder.fixed64_str(7, &self.start_time_unix_nano);
+        encoder.fixed64_str(8, &self.end_time_unix_nano);
+        encoder.messages(9, &self.attributes);
+        encoder.int64(10, self.dropped_attributes_count);
+        encoder.messages(11, &self.events);
+        encoder.int64(12, self.dropped_events_count);
+        encoder.messages(13, &self.links);
+        encoder.int64(14, self.dropped_links_count);
+        encoder.message(15, &self.status);
+        encoder.fixed32(16, self.flags as u32);
+    }
+}
+
+impl ProtoEncode for Event {
+    fn encode(&self, encoder: &mut ProtoEncoder) {
+        encoder.fixed64_str(1, &self.time_unix_nano);
+        encoder.string(2, &self.name);
+        encoder.messages(3, &self.attributes);
+    }
+}
+
+impl ProtoEncode for Link {
+    fn encode(&self, _encoder: &mut ProtoEncoder) {}
+}
+
+impl ProtoEncode for Status {
+    fn encode(&self, encoder: &mut ProtoEncoder) {
+        encoder.string(2, &self.message);
+        encoder.int64(3, self.code);
+    }
+}
+
+impl ProtoEncode for ResourceMetricsRoot {
+    fn encode(&self, encoder: &mut ProtoEncoder) {
+        encoder.messages(1, &self.resource_metrics);
+    }
+}
+
+impl ProtoEncode for ResourceMetrics {
+    fn encode(&self, encoder: &mut ProtoEncoder) {
+        encoder.message(1, &self.resource);
+        encoder.messages(2, &self.scope_metrics);
+    }
+}
+
+impl ProtoEncode for ScopeMetrics {
+    fn encode(&self, encoder: &mut ProtoEncoder) {
+        encoder.message(1, &self.scope);
+        encoder.messages(2, &self.metrics);
+    }
+}
+
+impl ProtoEncode for Metric {
+    fn encode(&self, encoder: &mut ProtoEncoder) {
+        encoder.string(1, &self.name);
+        encoder.string(2, &self.description);
+        encoder.string(3, &self.unit);
+        match &self.data {
+            MetricData::Gauge(gauge) => encoder.message(5, gauge),
+            MetricData::Sum(sum) => encoder.message(7, sum),
+            MetricData::Histogram(histogram) => encoder.message(9, histogram),
+        }
+    }
+}
+
+impl ProtoEncode for GaugeData {
+    fn encode(&self, encoder: &mut ProtoEncoder) {
+        encoder.messages(1, &self.data_points);
+    }
+}
+
+impl ProtoEncode for Sum {
+    fn encode(&self, encoder: &mut ProtoEncoder) {
+        encoder.messages(1, &self.data_points);
+        encoder.int64(2, self.aggregation_temporality);
+        encoder.bool(3, self.is_monotonic);
+    }
+}
+
+impl ProtoEncode for DataPoint {
+    fn encode(&self, encoder: &mut ProtoEncoder) {
+        encoder.fixed64_str(2, &self.start_time_unix_nano);
+        encoder.fixed64_str(3, &self.time_unix_nano);
+        match self.value {
+            NumberValue::Double(value) => encoder.fixed64_field(4, value.to_le_bytes()),
+            NumberValue::Int(value) => encoder.fixed64_field(6, value.to_le_bytes()),
+        }
+        encoder.messages(7, &self.attributes);
+    }
+}
+
+impl ProtoEncode for HistogramData {
+    fn encode(&self, encoder: &mut ProtoEncoder) {
+        encoder.messages(1, &self.data_points);
+        encoder.int64(2, self.aggregation_temporality);
+    }
+}
+
+impl ProtoEncode for HistogramDataPoint {
+    fn encode(&self, encoder: &mut ProtoEncoder) {
+        encoder.fixed64_str(2, &self.start_time_unix_nano);
+        encoder.fixed64_str(3, &self.time_unix_nano);
+        encoder.fixed64(4, self.count);
+        encoder.optional_double(5, self.sum);
+        encoder.packed_fixed64(6, &self.bucket_counts);
+        encoder.packed_double(7, &self.explicit_bounds);
+        encoder.messages(9, &self.attributes);
+        if let Some(min) = self.min {
+            encoder.optional_double(11, min);
+        }
+        if let Some(max) = self.max {
+            encoder.optional_double(12, max);
+        }
+    }
+}
+
+impl ProtoEncode for ResourceLogsRoot {
+    fn encode(&self, encoder: &mut ProtoEncoder) {
+        encoder.messages(1, &self.resource_logs);
+    }
+}
+
+impl ProtoEncode for ResourceLogs {
+    fn encode(&self, encoder: &mut ProtoEncoder) {
+        encoder.message(1, &self.resource);
+        encoder.messages(2, &self.scope_logs);
+    }
+}
+
+impl ProtoEncode for ScopeLogs {
+    fn encode(&self, encoder: &mut ProtoEncoder) {
+        encoder.message(1, &self.scope);
+        encoder.messages(2, &self.log_records);
+    }
+}
+
+impl ProtoEncode for LogRecord {
+    fn encode(&self, encoder: &mut ProtoEncoder) {
+        encoder.fixed64_str(1, &self.time_unix_nano);
+        encoder.int64(2, self.severity_number);
+        encoder.string(3, &self.severity_text);
+        encoder.message(5, &self.body);
+        encoder.messages(6, &self.attributes);
+        encoder.int64(7, self.dropped_attributes_count);
+        encoder.fixed32(8, self.flags as u32);
+        encoder.hex_bytes(9, &self.trace_id);
+        encoder.hex_bytes(10, &self.span_id);
+        encoder.fixed64_str(11, &self.observed_time_unix_nano);
+    }
+}
+
+/// Reads one varint from the front of `bytes`, advancing it
+fn read_varint(bytes: &mut &[u8]) -> Option<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = bytes.split_first()?;
+        *bytes = rest;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+enum FieldValue<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+}
+
+/// Calls `visit` with the number and payload of every varint or length-delimited field, skipping fixed-width ones
+fn read_fields<'a>(mut bytes: &'a [u8], mut visit: impl FnMut(u64, FieldValue<'a>)) -> Option<()> {
+    while !bytes.is_empty() {
+        let tag = read_varint(&mut bytes)?;
+        let field = tag >> 3;
+        match (tag & 0x7) as u8 {
+            WIRE_VARINT => visit(field, FieldValue::Varint(read_varint(&mut bytes)?)),
+            WIRE_FIXED64 => bytes = bytes.get(8..)?,
+            WIRE_LENGTH_DELIMITED => {
+                let len = read_varint(&mut bytes)? as usize;
+                visit(field, FieldValue::Bytes(bytes.get(..len)?));
+                bytes = &bytes[len..];
+            }
+            WIRE_FIXED32 => bytes = bytes.get(4..)?,
+            _ => return None,
+        }
+    }
+    Some(())
+}
+
+/// Decodes `partial_success` (field 1) of an `Export*ServiceResponse` into the rejected count and error message.
+/// The trace, metric and log responses share the same layout, so one decoder covers all three.
+pub(crate) fn decode_partial_success(bytes: &[u8]) -> Option<(u64, String)> {
+    let mut partial_success = None;
+    read_fields(bytes, |field, value| {
+        if let (1, FieldValue::Bytes(message)) = (field, value) {
+            partial_success = Some(message);
+        }
+    })?;
+
+    let mut rejected = 0;
+    let mut error_message = String::new();
+    read_fields(partial_success?, |field, value| match (field, value) {
+        (1, FieldValue::Varint(count)) => rejected = count,
+        (2, FieldValue::Bytes(message)) => error_message = String::from_utf8_lossy(message).into_owned(),
+        _ => {}
+    })?;
+    Some((rejected, error_message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Expected encodings, written out from the field numbers and wire types in opentelemetry-proto v1
+
+    const RESOURCE_SPANS: &[u8] = &[
+        0x0a, 0xda, 0x01, 0x0a, 0x17, 0x0a, 0x15, 0x0a, 0x0c, 0x73, 0x65, 0x72, 0x76, 0x69, 0x63, 0x65,
+        0x2e, 0x6e, 0x61, 0x6d, 0x65, 0x12, 0x05, 0x0a, 0x03, 0x73, 0x76, 0x63, 0x12, 0xbe, 0x01, 0x0a,
+        0x12, 0x0a, 0x09, 0x73, 0x6d, 0x6f, 0x6c, 0x5f, 0x6f, 0x74, 0x65, 0x6c, 0x12, 0x05, 0x30, 0x2e,
+        0x31, 0x2e, 0x30, 0x12, 0xa7, 0x01, 0x0a, 0x10, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08,
+        0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f, 0x10, 0x12, 0x08, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16,
+        0x17, 0x18, 0x22, 0x08, 0x21, 0x22, 0x23, 0x24, 0x25, 0x26, 0x27, 0x28, 0x2a, 0x05, 0x47, 0x45,
+        0x54, 0x20, 0x2f, 0x30, 0x02, 0x39, 0xe8, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x41, 0xd0,
+        0x07, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x4a, 0x17, 0x0a, 0x10, 0x68, 0x74, 0x74, 0x70, 0x2e,
+        0x73, 0x74, 0x61, 0x74, 0x75, 0x73, 0x5f, 0x63, 0x6f, 0x64, 0x65, 0x12, 0x03, 0x18, 0xc8, 0x01,
+        0x4a, 0x0c, 0x0a, 0x06, 0x63, 0x61, 0x63, 0x68, 0x65, 0x64, 0x12, 0x02, 0x10, 0x01, 0x4a, 0x12,
+        0x0a, 0x05, 0x72, 0x61, 0x74, 0x69, 0x6f, 0x12, 0x09, 0x21, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0xe0, 0x3f, 0x5a, 0x1f, 0x09, 0xdc, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x12, 0x05, 0x72,
+        0x65, 0x74, 0x72, 0x79, 0x1a, 0x0d, 0x0a, 0x07, 0x61, 0x74, 0x74, 0x65, 0x6d, 0x70, 0x74, 0x12,
+        0x02, 0x18, 0x02, 0x7a, 0x02, 0x18, 0x01, 0x85, 0x01, 0x00, 0x03, 0x00, 0x00,
+    ];
+
+    const RESOURCE_METRICS: &[u8] = &[
+        0x0a, 0xc1, 0x02, 0x0a, 0x17, 0x0a, 0x15, 0x0a, 0x0c, 0x73, 0x65, 0x72, 0x76, 0x69, 0x63, 0x65,
+        0x2e, 0x6e, 0x61, 0x6d, 0x65, 0x12, 0x05, 0x0a, 0x03, 0x73, 0x76, 0x63, 0x12, 0xa5, 0x02, 0x0a,
+        0x12, 0x0a, 0x09, 0x73, 0x6d, 0x6f, 0x6c, 0x5f, 0x6f, 0x74, 0x65, 0x6c, 0x12, 0x05, 0x30, 0x2e,
+        0x31, 0x2e, 0x30, 0x12, 0x6e, 0x0a, 0x08, 0x72, 0x65, 0x71, 0x75, 0x65, 0x73, 0x74, 0x73, 0x12,
+        0x10, 0x48, 0x61, 0x6e, 0x64, 0x6c, 0x65, 0x64, 0x20, 0x72, 0x65, 0x71, 0x75, 0x65, 0x73, 0x74,
+        0x73, 0x1a, 0x01, 0x31, 0x3a, 0x4d, 0x0a, 0x2a, 0x11, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x19, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x31, 0x05, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x3a, 0x0d, 0x0a, 0x05, 0x72, 0x6f, 0x75, 0x74, 0x65, 0x12, 0x04, 0x0a, 0x02,
+        0x2f, 0x61, 0x0a, 0x1b, 0x11, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x19, 0x02, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x31, 0xfd, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x10,
+        0x02, 0x18, 0x01, 0x12, 0x28, 0x0a, 0x0b, 0x74, 0x65, 0x6d, 0x70, 0x65, 0x72, 0x61, 0x74, 0x75,
+        0x72, 0x65, 0x1a, 0x03, 0x43, 0x65, 0x6c, 0x2a, 0x14, 0x0a, 0x12, 0x19, 0x02, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x21, 0x00, 0x00, 0x00, 0x00, 0x00, 0x80, 0x35, 0x40, 0x12, 0x75, 0x0a,
+        0x07, 0x6c, 0x61, 0x74, 0x65, 0x6e, 0x63, 0x79, 0x1a, 0x02, 0x6d, 0x73, 0x4a, 0x66, 0x0a, 0x62,
+        0x11, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x19, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x21, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x29, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x18, 0x40, 0x32, 0x18, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x3a, 0x10,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xf0, 0x3f, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x14, 0x40,
+        0x59, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xf0, 0x3f, 0x61, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x08, 0x40, 0x10, 0x01,
+    ];
+
+    const PARTIAL_SUCCESS: &[u8] = &[
+        0x0a, 0x1a, 0x08, 0x03, 0x12, 0x16, 0x33, 0x20, 0x73, 0x70, 0x61, 0x6e, 0x73, 0x20, 0x77, 0x65,
+        0x72, 0x65, 0x20, 0x74, 0x6f, 0x6f, 0x20, 0x6c, 0x61, 0x72, 0x67, 0x65,
+    ];
+
+    fn resource() -> Resource {
+        Resource {
+            attributes: vec![Attribute::new("service.name", "svc")],
+            dropped_attributes_count: 0,
+        }
+    }
+
+    fn scope() -> Scope {
+        Scope {
+            name: "smol_otel".to_string(),
+            version: "0.1.0".to_string(),
+            attributes: vec![],
+            dropped_attributes_count: 0,
+        }
+    }
+
+    #[test]
+    fn encodes_resource_spans() {
+        let span = Span {
+            trace_id: "0102030405060708090a0b0c0d0e0f10".to_string(),
+            span_id: "1112131415161718".to_string(),
+            parent_span_id: "2122232425262728".to_string(),
+            name: "GET /".to_string(),
+            start_time_unix_nano: "1000".to_string(),
+            end_time_unix_nano: "2000".to_string(),
+            kind: SpanKind::Server as i64,
+            attributes: vec![
+                Attribute::new("http.status_code", 200i64),
+                Attribute::new("cached", true),
+                Attribute::new("ratio", 0.5),
+            ],
+            events: vec![Event {
+                name: "retry".to_string(),
+                time_unix_nano: "1500".to_string(),
+                attributes: vec![Attribute::new("attempt", 2i64)],
+            }],
+            trace_state: String::new(),
+            flags: SpanFlags::ContextHasIsRemote as i64 | SpanFlags::ContextIsRemote as i64,
+            dropped_attributes_count: 0,
+            dropped_events_count: 0,
+            links: vec![],
+            dropped_links_count: 0,
+            status: Status {
+                message: String::new(),
+                code: StatusCode::Ok as i64,
+            },
+        };
+        let root = ResourceSpansRoot {
+            resource_spans: vec![ResourceSpan {
+                resource: resource(),
+                scope_spans: vec![ScopeSpan { scope: scope(), spans: vec![span] }],
+            }],
+        };
+        assert_eq!(root.encode_to_vec(), RESOURCE_SPANS);
+    }
+
+    #[test]
+    fn encodes_resource_metrics() {
+        let requests = Metric {
+            name: "requests".to_string(),
+            description: "Handled requests".to_string(),
+            unit: "1".to_string(),
+            data: MetricData::Sum(Sum {
+                aggregation_temporality: Temporality::Cumulative as i64,
+                is_monotonic: true,
+                data_points: vec![
+                    DataPoint {
+                        attributes: vec![Attribute::new("route", "/a")],
+                        start_time_unix_nano: "1".to_string(),
+                        time_unix_nano: "2".to_string(),
+                        value: NumberValue::Int(5),
+                    },
+                    DataPoint {
+                        attributes: vec![],
+                        start_time_unix_nano: "1".to_string(),
+                        time_unix_nano: "2".to_string(),
+                        value: NumberValue::Int(-3),
+                    },
+                ],
+            }),
+        };
+        let temperature = Metric {
+            name: "temperature".to_string(),
+            description: String::new(),
+            unit: "Cel".to_string(),
+            data: MetricData::Gauge(GaugeData {
+                data_points: vec![DataPoint {
+                    attributes: vec![],
+                    start_time_unix_nano: String::new(),
+                    time_unix_nano: "2".to_string(),
+                    value: NumberValue::Double(21.5),
+                }],
+            }),
+        };
+        let latency = Metric {
+            name: "latency".to_string(),
+            description: String::new(),
+            unit: "ms".to_string(),
+            data: MetricData::Histogram(HistogramData {
+                aggregation_temporality: Temporality::Delta as i64,
+                data_points: vec![HistogramDataPoint {
+                    attributes: vec![],
+                    start_time_unix_nano: "1".to_string(),
+                    time_unix_nano: "2".to_string(),
+                    count: 3,
+                    sum: 6.0,
+                    bucket_counts: vec![1, 2, 0],
+                    explicit_bounds: vec![1.0, 5.0],
+                    min: Some(1.0),
+                    max: Some(3.0),
+                }],
+            }),
+        };
+        let root = ResourceMetricsRoot {
+            resource_metrics: vec![ResourceMetrics {
+                resource: resource(),
+                scope_metrics: vec![ScopeMetrics {
+                    scope: scope(),
+                    metrics: vec![requests, temperature, latency],
+                }],
+            }],
+        };
+        assert_eq!(root.encode_to_vec(), RESOURCE_METRICS);
+    }
+
+    #[test]
+    fn decodes_partial_success() {
+        assert_eq!(decode_partial_success(PARTIAL_SUCCESS), Some((3, "3 spans were too large".to_string())));
+    }
+
+    #[test]
+    fn decodes_empty_partial_success() {
+        // A response without partial_success, and one with an empty partial_success message
+        assert_eq!(decode_partial_success(&[]), None);
+        assert_eq!(decode_partial_success(&[0x0a, 0x00]), Some((0, String::new())));
+    }
+
+    #[test]
+    fn skips_unknown_fields_in_partial_success() {
+        // Unknown fixed64 field 7, then partial_success with an unknown fixed32 field 9 before rejected = 1
+        let bytes = [
+            0x39, 0, 0, 0, 0, 0, 0, 0, 0,
+            0x0a, 0x07, 0x4d, 0, 0, 0, 0, 0x08, 0x01,
+        ];
+        assert_eq!(decode_partial_success(&bytes), Some((1, String::new())));
+    }
+
+    #[test]
+    fn rejects_truncated_partial_success() {
+        assert_eq!(decode_partial_success(&PARTIAL_SUCCESS[..PARTIAL_SUCCESS.len() - 1]), None);
+    }
+}
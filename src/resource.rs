@@ -0,0 +1,181 @@
+use std::fs;
+
+use crate::structs::{AnyValue, Attribute};
+
+/// Source of resource attributes discovered from the running environment
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResourceDetector {
+    /// `host.name` and `host.arch`
+    Host,
+    /// `process.pid`, `process.executable.*` and `process.command_args`
+    Process,
+    /// `os.type`
+    Os,
+    /// `container.id` read from `/proc/self/cgroup` or `/proc/self/mountinfo`, Linux only
+    Container,
+}
+
+impl ResourceDetector {
+    pub const ALL: [ResourceDetector; 4] = [
+        ResourceDetector::Host,
+        ResourceDetector::Process,
+        ResourceDetector::Os,
+        ResourceDetector::Container,
+    ];
+
+    /// Attributes this detector could determine, missing values are left out
+    pub fn detect(&self) -> Vec<Attribute> {
+        match self {
+            ResourceDetector::Host => detect_host(),
+            ResourceDetector::Process => detect_process(),
+            ResourceDetector::Os => vec![Attribute::new("os.type", os_type())],
+            ResourceDetector::Container => container_id()
+                .map(|id| vec![Attribute::new("container.id", id)])
+                .unwrap_or_default(),
+        }
+    }
+}
+
+fn detect_host() -> Vec<Attribute> {
+    let mut attributes = vec![Attribute::new("host.arch", host_arch())];
+    let host_name = fs::read_to_string("/proc/sys/kernel/hostname").ok()
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .or_else(|| std::env::var("COMPUTERNAME").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty());
+    if let Some(host_name) = host_name {
+        attributes.push(Attribute::new("host.name", host_name));
+    }
+    attributes
+}
+
+fn detect_process() -> Vec<Attribute> {
+    let mut attributes = vec![Attribute::new("process.pid", std::process::id() as i64)];
+    if let Ok(executable) = std::env::current_exe() {
+        if let Some(name) = executable.file_name() {
+            attributes.push(Attribute::new("process.executable.name", name.to_string_lossy().into_owned()));
+        }
+        attributes.push(Attribute::new("process.executable.path", executable.to_string_lossy().into_owned()));
+    }
+    let args: Vec<AnyValue> = std::env::args().map(AnyValue::from).collect();
+    attributes.push(Attribute::new("process.command_args", args));
+    attributes
+}
+
+/// Maps Rust's target names to the semantic convention values
+fn host_arch() -> &'static str {
+    match std::env::consts::ARCH {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        "x86" => "x86",
+        "arm" => "arm32",
+        "powerpc64" => "ppc64",
+        "s390x" => "s390x",
+        other => other,
+    }
+}
+
+fn os_type() -> &'static str {
+    match std::env::consts::OS {
+        "macos" => "darwin",
+        other => other,
+    }
+}
+
+/// Container runtimes that name the cgroup scope of a container `<prefix>-<id>.scope`
+const CGROUP_SCOPE_PREFIXES: [&str; 4] = ["docker-", "cri-containerd-", "crio-", "libpod-"];
+
+/// Reads the id from the container's cgroup path or, for cgroup v2 where that path is usually just `/`,
+/// from the per-container files the runtime bind mounts into it
+fn container_id() -> Option<String> {
+    let from_cgroup = || fs::read_to_string("/proc/self/cgroup").ok()?
+        .lines()
+        .find_map(cgroup_container_id);
+    let from_mountinfo = || fs::read_to_string("/proc/self/mountinfo").ok()?
+        .lines()
+        .find_map(mountinfo_container_id);
+    from_cgroup().or_else(from_mountinfo)
+}
+
+fn is_container_id(value: &str) -> bool {
+    value.len() == 64 && value.bytes().all(|byte| byte.is_ascii_hexdigit())
+}
+
+/// `hierarchy-id:controllers:path` where the last path segment is `docker-<id>.scope`, `cri-containerd-<id>.scope`
+/// and similar, or the bare id as in `/docker/<id>` and `/kubepods/<pod>/<id>`
+fn cgroup_container_id(line: &str) -> Option<String> {
+    let path = line.splitn(3, ':').nth(2)?;
+    let segment = path.rsplit('/').next()?;
+    let name = segment.strip_suffix(".scope").unwrap_or(segment);
+    let id = CGROUP_SCOPE_PREFIXES.iter()
+        .find_map(|prefix| name.strip_prefix(prefix))
+        .unwrap_or(name);
+    is_container_id(id).then(|| id.to_string())
+}
+
+/// Mount roots like `/var/lib/docker/containers/<id>/hostname`, other mounts such as the overlay root carry layer
+/// ids that are not the container id
+fn mountinfo_container_id(line: &str) -> Option<String> {
+    let root = line.split(' ').nth(3)?;
+    if !root.ends_with("/hostname") && !root.ends_with("/resolv.conf") {
+        return None;
+    }
+    let mut segments = root.split('/');
+    while let Some(segment) = segments.next() {
+        if segment == "containers" || segment == "overlay-containers" {
+            return segments.next().filter(|id| is_container_id(id)).map(str::to_string);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ID: &str = "9a2d3b8e0b6f5c4a1e7d2f3c4b5a69788796a5b4c3d2e1f0a9b8c7d6e5f4a3b2";
+    const LAYER: &str = "4f3e2d1c0b9a8f7e6d5c4b3a29180706f5e4d3c2b1a0f9e8d7c6b5a493827160";
+
+    #[test]
+    fn reads_cgroup_v1_paths() {
+        for line in [
+            format!("12:memory:/docker/{}", ID),
+            format!("11:cpu,cpuacct:/kubepods/besteffort/pod6c2d0f5e-0c3a-4a8b-9f5e-1d2c3b4a5f6e/{}", ID),
+            format!("0::/system.slice/docker-{}.scope", ID),
+            format!("1:name=systemd:/kubepods.slice/kubepods-pod1.slice/cri-containerd-{}.scope", ID),
+            format!("5:pids:/kubepods.slice/kubepods-burstable.slice/crio-{}.scope", ID),
+        ] {
+            assert_eq!(cgroup_container_id(&line).as_deref(), Some(ID), "{}", line);
+        }
+    }
+
+    #[test]
+    fn ignores_cgroup_paths_without_a_container() {
+        for line in [
+            "0::/".to_string(),
+            "0::/user.slice/user-1000.slice/session-2.scope".to_string(),
+            format!("0::/system.slice/{}-mount.scope", ID),
+        ] {
+            assert_eq!(cgroup_container_id(&line), None, "{}", line);
+        }
+    }
+
+    #[test]
+    fn reads_id_from_container_mounts_not_overlay_layers() {
+        let overlay = format!(
+            "1360 1279 0:58 / / rw,relatime master:1 - overlay overlay rw,lowerdir=/var/lib/docker/overlay2/l/ABC,upperdir=/var/lib/docker/overlay2/{}/diff,workdir=/var/lib/docker/overlay2/{}/work",
+            LAYER, LAYER,
+        );
+        let resolv_conf = format!("1385 1360 254:1 /var/lib/docker/containers/{}/resolv.conf /etc/resolv.conf rw,relatime - ext4 /dev/vda1 rw", ID);
+        let hostname = format!("1386 1360 254:1 /var/lib/containers/storage/overlay-containers/{}/userdata/hostname /etc/hostname rw - xfs /dev/sda1 rw", ID);
+        let layer_mount = format!("1387 1360 254:1 /var/lib/docker/overlay2/{}/hostname /etc/hostname rw - ext4 /dev/vda1 rw", LAYER);
+
+        assert_eq!(mountinfo_container_id(&overlay), None);
+        assert_eq!(mountinfo_container_id(&layer_mount), None);
+        assert_eq!(mountinfo_container_id(&resolv_conf).as_deref(), Some(ID));
+        assert_eq!(mountinfo_container_id(&hostname).as_deref(), Some(ID));
+
+        let mountinfo = [overlay, resolv_conf].join("\n");
+        assert_eq!(mountinfo.lines().find_map(mountinfo_container_id).as_deref(), Some(ID));
+    }
+}
@@ -0,0 +1,108 @@
+use std::fmt;
+use std::sync::Arc;
+
+use miniserde::{json, Deserialize};
+
+use crate::protobuf;
+use crate::tracer::Protocol;
+
+/// Items a collector accepted the request for but rejected, from an OTLP partial success response
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PartialSuccess {
+    /// `traces`, `metrics` or `logs`
+    pub signal: &'static str,
+    /// Rejected spans, data points or log records
+    pub rejected: u64,
+    pub error_message: String,
+}
+
+/// Callback invoked for every partial success response
+#[derive(Clone)]
+pub(crate) struct PartialSuccessHandler(pub Arc<dyn Fn(&PartialSuccess) + Send + Sync>);
+
+impl fmt::Debug for PartialSuccessHandler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PartialSuccessHandler")
+    }
+}
+
+#[derive(Deserialize)]
+struct ExportResponse {
+    #[serde(rename = "partialSuccess")]
+    partial_success: Option<json::Object>,
+}
+
+/// Rejected count and error message of a successful export response, `None` if everything was accepted
+pub(crate) fn parse_partial_success(protocol: Protocol, body: &[u8]) -> Option<(u64, String)> {
+    let (rejected, error_message) = match protocol {
+        Protocol::HttpProtobuf => protobuf::decode_partial_success(body)?,
+        Protocol::HttpJson => {
+            let response: ExportResponse = json::from_str(std::str::from_utf8(body).ok()?).ok()?;
+            let partial_success = response.partial_success?;
+
+            // rejectedSpans, rejectedDataPoints or rejectedLogRecords, int64 may be a string or a number
+            let rejected = partial_success.iter()
+                .find(|(key, _)| key.starts_with("rejected"))
+                .and_then(|(_, value)| match value {
+                    json::Value::String(value) => value.parse().ok(),
+                    json::Value::Number(json::Number::U64(value)) => Some(*value),
+                    json::Value::Number(json::Number::I64(value)) => u64::try_from(*value).ok(),
+                    _ => None,
+                })
+                .unwrap_or(0);
+            let error_message = match partial_success.get("errorMessage") {
+                Some(json::Value::String(message)) => message.clone(),
+                _ => String::new(),
+            };
+            (rejected, error_message)
+        }
+    };
+
+    // An empty partial_success message means full success
+    if rejected == 0 && error_message.is_empty() {
+        return None;
+    }
+    Some((rejected, error_message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_json(body: &str) -> Option<(u64, String)> {
+        parse_partial_success(Protocol::HttpJson, body.as_bytes())
+    }
+
+    #[test]
+    fn rejected_count_may_be_a_string_or_a_number() {
+        let expected = Some((3, "invalid span".to_string()));
+        assert_eq!(parse_json(r#"{"partialSuccess":{"rejectedSpans":"3","errorMessage":"invalid span"}}"#), expected);
+        assert_eq!(parse_json(r#"{"partialSuccess":{"rejectedSpans":3,"errorMessage":"invalid span"}}"#), expected);
+        assert_eq!(parse_json(r#"{"partialSuccess":{"rejectedDataPoints":"2"}}"#), Some((2, String::new())));
+        assert_eq!(parse_json(r#"{"partialSuccess":{"rejectedLogRecords":1}}"#), Some((1, String::new())));
+    }
+
+    #[test]
+    fn missing_error_message_is_empty() {
+        assert_eq!(parse_json(r#"{"partialSuccess":{"rejectedSpans":"5"}}"#), Some((5, String::new())));
+    }
+
+    #[test]
+    fn message_without_rejected_items_is_reported() {
+        assert_eq!(parse_json(r#"{"partialSuccess":{"errorMessage":"deprecated field"}}"#), Some((0, "deprecated field".to_string())));
+    }
+
+    #[test]
+    fn empty_partial_success_is_full_success() {
+        assert_eq!(parse_json(r#"{"partialSuccess":{}}"#), None);
+        assert_eq!(parse_json(r#"{"partialSuccess":{"rejectedSpans":"0","errorMessage":""}}"#), None);
+        assert_eq!(parse_json("{}"), None);
+    }
+
+    #[test]
+    fn non_json_body_is_full_success() {
+        assert_eq!(parse_json(""), None);
+        assert_eq!(parse_json("OK"), None);
+        assert_eq!(parse_partial_success(Protocol::HttpJson, &[0xff, 0xfe]), None);
+    }
+}
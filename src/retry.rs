@@ -0,0 +1,190 @@
+use std::fmt;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use rand::Rng as _;
+use smol::Timer;
+
+#[derive(Clone, Debug)]
+pub struct RetryConfig {
+    /// Delay before the first retry
+    pub initial_backoff: Duration,
+    /// Upper bound for the delay between two attempts
+    pub max_backoff: Duration,
+    /// Total time after which a retryable failure is given up on. This bounds the whole export, while
+    /// [`ExportConfig::timeout`](crate::ExportConfig::timeout) bounds each attempt.
+    pub max_elapsed_time: Duration,
+    /// Factor the delay grows by after each attempt
+    pub multiplier: f64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+            max_elapsed_time: Duration::from_secs(60),
+            multiplier: 2.0,
+        }
+    }
+}
+
+impl RetryConfig {
+    /// Every export is attempted exactly once
+    pub fn disabled() -> Self {
+        Self {
+            max_elapsed_time: Duration::ZERO,
+            ..Self::default()
+        }
+    }
+}
+
+/// Final outcome of a failed export, returned boxed so callers can downcast it
+#[derive(Debug)]
+pub enum ExportError {
+    /// The collector rejected the request in a way retrying will not fix, e.g. a 400
+    Permanent { message: String },
+    /// Every attempt failed with a retryable error until the max elapsed time ran out
+    RetriesExhausted { attempts: u32, message: String },
+    /// The last attempt did not finish within the configured timeout and there was no time left to retry
+    Timeout { timeout: Duration },
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportError::Permanent { message } => write!(f, "permanent export failure: {}", message),
+            ExportError::RetriesExhausted { attempts, message } => {
+                write!(f, "export failed after {} attempts: {}", attempts, message)
+            }
+            ExportError::Timeout { timeout } => write!(f, "export timed out after {:?}", timeout),
+        }
+    }
+}
+
+impl std::error::Error for ExportError {}
+
+/// Outcome of a single export attempt
+pub(crate) enum AttemptError {
+    /// 429, 502, 503, 504 or a connection error, `retry_after` is the server's requested delay
+    Retryable { message: String, retry_after: Option<Duration> },
+    Permanent { message: String },
+    /// The attempt did not finish within the configured timeout, retried like a connection error
+    Timeout { timeout: Duration },
+}
+
+/// Picks a delay in `[backoff / 2, backoff]` so clients failing together do not retry together
+fn jitter(backoff: Duration) -> Duration {
+    let half = backoff / 2;
+    half + half.mul_f64(rand::thread_rng().gen::<f64>())
+}
+
+/// Calls `attempt` until it succeeds, fails permanently or `config.max_elapsed_time` would be exceeded
+pub(crate) async fn with_retries<T, F, Fut>(config: &RetryConfig, mut attempt: F) -> Result<T, ExportError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, AttemptError>>,
+{
+    let start = Instant::now();
+    let mut backoff = config.initial_backoff;
+    let mut attempts = 0;
+
+    loop {
+        attempts += 1;
+        let (message, retry_after, timeout) = match attempt().await {
+            Ok(value) => return Ok(value),
+            Err(AttemptError::Permanent { message }) => return Err(ExportError::Permanent { message }),
+            Err(AttemptError::Retryable { message, retry_after }) => (message, retry_after, None),
+            Err(AttemptError::Timeout { timeout }) => (format!("attempt timed out after {:?}", timeout), None, Some(timeout)),
+        };
+
+        let delay = retry_after.unwrap_or_else(|| jitter(backoff));
+        if start.elapsed() + delay > config.max_elapsed_time {
+            return Err(match timeout {
+                Some(timeout) => ExportError::Timeout { timeout },
+                None => ExportError::RetriesExhausted { attempts, message },
+            });
+        }
+
+        log::warn!("export attempt {} failed, retrying in {:?}: {}", attempts, delay, message);
+        Timer::after(delay).await;
+        backoff = backoff.mul_f64(config.multiplier).min(config.max_backoff);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    fn config(max_elapsed_time: Duration) -> RetryConfig {
+        RetryConfig {
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(5),
+            max_elapsed_time,
+            multiplier: 2.0,
+        }
+    }
+
+    fn retryable() -> AttemptError {
+        AttemptError::Retryable { message: "503".to_string(), retry_after: None }
+    }
+
+    #[test]
+    fn retries_until_success() {
+        let attempts = Cell::new(0);
+        let result = smol::block_on(with_retries(&config(Duration::from_secs(5)), || {
+            attempts.set(attempts.get() + 1);
+            let attempt = attempts.get();
+            async move {
+                match attempt {
+                    1 => Err(retryable()),
+                    2 => Err(AttemptError::Timeout { timeout: Duration::from_millis(10) }),
+                    _ => Ok(attempt),
+                }
+            }
+        }));
+        assert_eq!(result.unwrap(), 3);
+    }
+
+    #[test]
+    fn permanent_failure_is_not_retried() {
+        let attempts = Cell::new(0);
+        let result = smol::block_on(with_retries(&config(Duration::from_secs(5)), || {
+            attempts.set(attempts.get() + 1);
+            async { Err::<(), _>(AttemptError::Permanent { message: "400".to_string() }) }
+        }));
+        assert!(matches!(result, Err(ExportError::Permanent { message }) if message == "400"));
+        assert_eq!(attempts.get(), 1);
+    }
+
+    #[test]
+    fn gives_up_after_max_elapsed_time() {
+        let result = smol::block_on(with_retries(&config(Duration::from_millis(30)), || async { Err::<(), _>(retryable()) }));
+        assert!(matches!(result, Err(ExportError::RetriesExhausted { attempts, message }) if attempts > 1 && message == "503"));
+    }
+
+    #[test]
+    fn timed_out_attempts_are_retried_then_reported_as_timeout() {
+        let attempts = Cell::new(0);
+        let timeout = Duration::from_millis(10);
+        let result = smol::block_on(with_retries(&config(Duration::from_millis(30)), || {
+            attempts.set(attempts.get() + 1);
+            async move { Err::<(), _>(AttemptError::Timeout { timeout }) }
+        }));
+        assert!(matches!(result, Err(ExportError::Timeout { timeout: reported }) if reported == timeout));
+        assert!(attempts.get() > 1);
+    }
+
+    #[test]
+    fn disabled_makes_a_single_attempt() {
+        let attempts = Cell::new(0);
+        let result = smol::block_on(with_retries(&RetryConfig::disabled(), || {
+            attempts.set(attempts.get() + 1);
+            async { Err::<(), _>(retryable()) }
+        }));
+        assert!(matches!(result, Err(ExportError::RetriesExhausted { attempts: 1, .. })));
+        assert_eq!(attempts.get(), 1);
+    }
+}
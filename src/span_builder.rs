@@ -4,6 +4,7 @@ use std::sync::Arc;
 use crate::tracer::OtlpTracer;
 use crate::globals;
 use crate::structs::*;
+use crate::span_context::SpanContext;
 use crate::span_guard::SpanGuard;
 
 pub struct SpanBuilder {
@@ -11,7 +12,8 @@ pub struct SpanBuilder {
     name: String,
     status: Status,
     kind: SpanKind,
-    attributes: HashMap<String, String>,
+    attributes: HashMap<String, AnyValue>,
+    remote_parent: Option<SpanContext>,
 }
 
 impl SpanBuilder {
@@ -22,10 +24,11 @@ impl SpanBuilder {
             status: Status { message: "".to_string(), code: StatusCode::Unset as i64 },
             kind: SpanKind::Internal,
             attributes: HashMap::new(),
+            remote_parent: None,
         }
     }
 
-    pub fn with_attribute(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+    pub fn with_attribute(mut self, key: impl Into<String>, value: impl Into<AnyValue>) -> Self {
         self.attributes.insert(
             key.into(), 
             value.into()
@@ -43,6 +46,12 @@ impl SpanBuilder {
         self
     }
 
+    /// Continues a trace started by another service, e.g. from [`crate::propagation::extract`]
+    pub fn with_remote_parent(mut self, context: SpanContext) -> Self {
+        self.remote_parent = Some(context);
+        self
+    }
+
     pub fn start(self) -> Arc<SpanGuard> {
         SpanGuard::start(
             &globals::executor(), 
@@ -50,7 +59,8 @@ impl SpanBuilder {
             &self.name,
             self.status,
             self.kind,
-            self.attributes
+            self.attributes,
+            self.remote_parent,
         )
     }
 }
\ No newline at end of file
@@ -1,5 +1,27 @@
-#[derive(Clone)]
+use crate::structs::TraceFlags;
+use crate::utilities;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct SpanContext {
     pub trace_id: String,
     pub span_id: String,
+    pub trace_flags: u8,
+    pub trace_state: String,
+    pub is_remote: bool,
+}
+
+impl SpanContext {
+    pub(crate) fn new_root() -> Self {
+        Self {
+            trace_id: utilities::generate_trace_id(),
+            span_id: utilities::generate_span_id(),
+            trace_flags: TraceFlags::Sampled as u8,
+            trace_state: String::new(),
+            is_remote: false,
+        }
+    }
+
+    pub fn is_sampled(&self) -> bool {
+        self.trace_flags & TraceFlags::Sampled as u8 != 0
+    }
 }
@@ -1,6 +1,6 @@
 use std::cell::RefCell;
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::sync::{Arc, Weak};
 use std::sync::Mutex as SyncMutex;
 
 use smol::Executor;
@@ -12,7 +12,17 @@ use crate::utilities;
 
 thread_local! {
     pub static CURRENT_SPAN_CONTEXT: RefCell<Option<SpanContext>> = RefCell::new(None);
-    pub static CURRENT_SPAN_GUARD: RefCell<Option<Arc<SpanGuard>>> = RefCell::new(None);
+    pub static CURRENT_SPAN_GUARD: RefCell<Option<Weak<SpanGuard>>> = RefCell::new(None);
+}
+
+/// Installs `context` and `guard` as the current span of this thread, returning what was there before
+pub(crate) fn swap_current(
+    context: Option<SpanContext>,
+    guard: Option<Weak<SpanGuard>>,
+) -> (Option<SpanContext>, Option<Weak<SpanGuard>>) {
+    let previous_context = CURRENT_SPAN_CONTEXT.with(|current| current.replace(context));
+    let previous_guard = CURRENT_SPAN_GUARD.with(|current| current.replace(guard));
+    (previous_context, previous_guard)
 }
 
 #[derive(Clone)]
@@ -27,10 +37,12 @@ pub struct SpanGuard {
     context: SpanContext,
     kind: SpanKind,
     parent_context: Option<SpanContext>,
+    previous_context: Option<SpanContext>,
+    previous_guard: Option<Weak<SpanGuard>>,
     thread_id: String,
     thread_name: String,
     events: Arc<SyncMutex<Vec<Event>>>,
-    attributes: Arc<SyncMutex<HashMap<String, String>>>,
+    attributes: Arc<SyncMutex<HashMap<String, AnyValue>>>,
     status: Arc<SyncMutex<Status>>,
 }
 
@@ -42,9 +54,11 @@ impl SpanGuard {
         status: Status,
         kind: SpanKind,
         events: Vec<Event>,
-        attributes: HashMap<String, String>,
+        attributes: HashMap<String, AnyValue>,
         context: SpanContext,
         parent_context: Option<SpanContext>,
+        previous_context: Option<SpanContext>,
+        previous_guard: Option<Weak<SpanGuard>>,
         location: &std::panic::Location<'_>,
         thread_id: String,
         thread_name: String,
@@ -60,6 +74,8 @@ impl SpanGuard {
             context,
             kind,
             parent_context,
+            previous_context,
+            previous_guard,
             thread_id,
             thread_name,
             events: Arc::new(SyncMutex::new(events)),
@@ -75,31 +91,33 @@ impl SpanGuard {
         name: &str,
         status: Status,
         kind: SpanKind,
-        attributes: HashMap<String, String>
+        attributes: HashMap<String, AnyValue>,
+        remote_parent: Option<SpanContext>,
     ) -> Arc<Self> {
         let location = std::panic::Location::caller();
         let thread = std::thread::current();
         let thread_id = format!("{:?}", thread.id());
         let thread_name = thread.name().unwrap_or("unnamed").to_string();
 
-        // Capture the parent context before creating new span
-        let parent_context = CURRENT_SPAN_CONTEXT.with(|current| current.borrow().clone());
+        // Capture the current span before creating new span, it is restored when this one ends
+        let previous_context = CURRENT_SPAN_CONTEXT.with(|current| current.borrow().clone());
+        let previous_guard = CURRENT_SPAN_GUARD.with(|current| current.borrow().clone());
+
+        // An incoming remote context takes precedence over the local parent
+        let parent_context = remote_parent.or_else(|| previous_context.clone());
         
         // Generate new span context
-        let trace_id = parent_context.as_ref()
-            .map(|parent| parent.trace_id.clone())
-            .unwrap_or_else(|| utilities::generate_trace_id());
-            
-        let new_context = SpanContext {
-            trace_id,
-            span_id: utilities::generate_span_id(),
+        let new_context = match parent_context.as_ref() {
+            Some(parent) => SpanContext {
+                trace_id: parent.trace_id.clone(),
+                span_id: utilities::generate_span_id(),
+                trace_flags: parent.trace_flags,
+                trace_state: parent.trace_state.clone(),
+                is_remote: false,
+            },
+            None => SpanContext::new_root(),
         };
         
-        // Set as current context
-        CURRENT_SPAN_CONTEXT.with(|current| {
-            *current.borrow_mut() = Some(new_context.clone());
-        });
-        
         // Create and wrap the guard
         let events = vec![];
         let guard = Arc::new(Self::new(
@@ -112,20 +130,44 @@ impl SpanGuard {
             attributes,
             new_context,
             parent_context,
+            previous_context,
+            previous_guard,
             location,
             thread_id,
             thread_name,
         ));
 
-        // Store Arc in thread local
-        CURRENT_SPAN_GUARD.with(|current| {
-            *current.borrow_mut() = Some(guard.clone());
-        });
+        // Set as current span, the thread local only holds a weak reference so dropping the guard ends the span
+        swap_current(Some(guard.context.clone()), Some(Arc::downgrade(&guard)));
 
         guard
     }
 
-    pub fn push_event(&self, level: log::Level, args: &std::fmt::Arguments) {
+    pub fn context(&self) -> &SpanContext {
+        &self.context
+    }
+
+    /// W3C trace flags in the low byte plus the OTLP bits recording whether the parent was remote
+    fn span_flags(&self) -> i64 {
+        let mut flags = self.context.trace_flags as i64 | SpanFlags::ContextHasIsRemote as i64;
+        if self.parent_context.as_ref().is_some_and(|parent| parent.is_remote) {
+            flags |= SpanFlags::ContextIsRemote as i64;
+        }
+        flags
+    }
+
+    /// Puts the previous span back as the current one, if this span is still the current one on this thread
+    pub(crate) fn exit_current(&self) {
+        let is_current = CURRENT_SPAN_CONTEXT.with(|current| {
+            current.borrow().as_ref().is_some_and(|ctx| ctx.span_id == self.context.span_id)
+        });
+        if is_current {
+            swap_current(self.previous_context.clone(), self.previous_guard.clone());
+        }
+    }
+
+    /// Records a log line as an event, `fields` are the record's structured key-values
+    pub fn push_event(&self, level: log::Level, args: &std::fmt::Arguments, fields: &[Attribute]) {
         let time = utilities::nanos();
         
         let event = Event {
@@ -134,7 +176,9 @@ impl SpanGuard {
             attributes: {
                 let mut map = HashMap::new();
                 map.insert("log.level".to_string(), level.to_string());
-                Attributes::from(map).0
+                let mut attributes = Attributes::from(map).0;
+                attributes.extend(fields.iter().cloned());
+                attributes
             },
         };
 
@@ -143,9 +187,21 @@ impl SpanGuard {
         }
     }
 
-    pub fn set_attribute(&self, key: &str, value: &str) {
+    pub fn add_event(&self, name: &str, attributes: Vec<Attribute>) {
+        let event = Event {
+            name: name.to_string(),
+            time_unix_nano: utilities::nanos().to_string(),
+            attributes,
+        };
+
+        if let Ok(mut events) = self.events.lock() {
+            events.push(event);
+        }
+    }
+
+    pub fn set_attribute(&self, key: &str, value: impl Into<AnyValue>) {
         if let Ok(mut attributes) = self.attributes.lock() {
-            attributes.insert(key.to_string(), value.to_string());
+            attributes.insert(key.to_string(), value.into());
         }
     }
 
@@ -163,38 +219,13 @@ impl Drop for SpanGuard {
         let end_time = utilities::nanos();
 
         // Restore parent context
-        if let Some(parent_ctx) = &self.parent_context {
-            CURRENT_SPAN_CONTEXT.with(|current| {
-                *current.borrow_mut() = Some(parent_ctx.clone());
-            });
-        } else {
-            // Only clear if no parent context
-            CURRENT_SPAN_GUARD.with(|current| {
-                *current.borrow_mut() = None;
-            });
-        }
+        self.exit_current();
 
-        // resource
-        let resource = Resource {
-            attributes: {
-                let mut map = HashMap::new();
-                map.insert("telemetry.sdk.language".to_string(), "rust".to_string());
-                map.insert("telemetry.sdk.version".to_string(), "0.1.0".to_string());
-                map.insert("telemetry.sdk.name".to_string(), "opentelemetry".to_string());
-                map.insert("service.name".to_string(), self.tracer.service_name.clone());
-                Attributes::from(map).0
-            },
-            dropped_attributes_count: 0,
-        };
+        // Unsampled traces are still propagated to children and downstream services, but never exported
+        if !self.context.is_sampled() {
+            return;
+        }
 
-        // scope
-        let scope = Scope {
-            name: env!("CARGO_PKG_NAME").to_string(),
-            version: env!("CARGO_PKG_VERSION").to_string(),
-            attributes: vec![],
-            dropped_attributes_count: 0,
-        };
-        
         // Prepare data for span
         let status = self.status.lock().unwrap().clone();
         let span_attributes = self.attributes.lock().unwrap().clone();
@@ -211,15 +242,15 @@ impl Drop for SpanGuard {
             start_time_unix_nano: self.start_time.to_string(),
             end_time_unix_nano: end_time.to_string(),
             kind: self.kind.clone() as i64,
-            flags: TraceFlags::Sampled as i64,
-            trace_state: "".to_string(),
+            flags: self.span_flags(),
+            trace_state: self.context.trace_state.clone(),
             attributes: {
                 let mut map = HashMap::new();
-                map.insert("code.filepath".to_string(), self.file.clone());
-                map.insert("code.lineno".to_string(), self.line.to_string());
-                map.insert("code.column".to_string(), self.column.to_string());
-                map.insert("thread.id".to_string(), self.thread_id.clone());
-                map.insert("thread.name".to_string(), self.thread_name.clone());
+                map.insert("code.filepath".to_string(), AnyValue::from(&self.file));
+                map.insert("code.lineno".to_string(), AnyValue::from(self.line));
+                map.insert("code.column".to_string(), AnyValue::from(self.column));
+                map.insert("thread.id".to_string(), AnyValue::from(&self.thread_id));
+                map.insert("thread.name".to_string(), AnyValue::from(&self.thread_name));
                 for (key, value) in span_attributes.iter() {
                     map.insert(key.clone(), value.clone());
                 }
@@ -233,25 +264,7 @@ impl Drop for SpanGuard {
             status
         };
 
-        // resource_span
-        let resource_span = ResourceSpan {
-            resource,
-            scope_spans: vec![ScopeSpan {
-                scope,
-                spans: vec![span],
-            }],
-        };
-        let resource_spans = vec![resource_span];
-
-        // Upload resource_spans
-        let tracer_clone = self.tracer.clone();
-        let executor_clone = self.executor.clone();
-        let handle = executor_clone.spawn(async move {
-            // Handle any errors here since we can't propagate them
-            if let Err(e) = tracer_clone.upload_traces(resource_spans).await {
-                eprintln!("Failed to upload span: {}", e);
-            }
-        });
-        handle.detach();
+        // Queue for batched upload
+        self.tracer.on_span_end(&self.executor, span);
     }
 }
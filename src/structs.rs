@@ -1,6 +1,9 @@
+use std::borrow::Cow;
 use std::collections::HashMap;
 
-use miniserde::Serialize;
+use miniserde::{ser, Serialize};
+
+use crate::utilities;
 
 #[allow(dead_code)]
 #[derive(Serialize, Clone)]
@@ -20,6 +23,13 @@ pub enum TraceFlags {
     Sampled = 1
 }
 
+#[derive(Serialize)]
+#[repr(i64)]
+pub enum SpanFlags {
+    ContextHasIsRemote = 0x100,
+    ContextIsRemote = 0x200,
+}
+
 #[derive(Serialize)]
 pub struct ResourceSpansRoot {
     #[serde(rename = "resourceSpans")]
@@ -33,23 +43,143 @@ pub struct ResourceSpan {
     pub scope_spans: Vec<ScopeSpan>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Clone, Debug)]
 pub struct Resource {
     pub attributes: Vec<Attribute>,
     #[serde(rename = "droppedAttributesCount")]
     pub dropped_attributes_count: i64,
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Clone, Debug, PartialEq)]
 pub struct Attribute {
     pub key: String,
-    pub value: AttributeValue,
+    pub value: AnyValue,
 }
 
-#[derive(Serialize, Clone)]
-pub struct AttributeValue {
-    #[serde(rename = "stringValue")]
-    pub string_value: String,
+impl Attribute {
+    pub fn new(key: impl Into<String>, value: impl Into<AnyValue>) -> Self {
+        Self { key: key.into(), value: value.into() }
+    }
+}
+
+/// Typed attribute value, serialized as the matching OTLP `AnyValue` field
+#[derive(Clone, Debug, PartialEq)]
+pub enum AnyValue {
+    String(String),
+    Bool(bool),
+    Int(i64),
+    Double(f64),
+    Bytes(Vec<u8>),
+    Array(Vec<AnyValue>),
+    KvList(Vec<Attribute>),
+}
+
+#[derive(Serialize)]
+struct ArrayValue {
+    values: Vec<AnyValue>,
+}
+
+#[derive(Serialize)]
+struct KeyValueList {
+    values: Vec<Attribute>,
+}
+
+struct AnyValueStream {
+    key: &'static str,
+    value: Box<dyn Serialize>,
+    done: bool,
+}
+
+impl ser::Map for AnyValueStream {
+    fn next(&mut self) -> Option<(Cow<str>, &dyn Serialize)> {
+        if self.done {
+            return None;
+        }
+        self.done = true;
+        Some((Cow::Borrowed(self.key), &*self.value))
+    }
+}
+
+impl Serialize for AnyValue {
+    fn begin(&self) -> ser::Fragment {
+        let (key, value): (&'static str, Box<dyn Serialize>) = match self {
+            AnyValue::String(value) => ("stringValue", Box::new(value.clone())),
+            AnyValue::Bool(value) => ("boolValue", Box::new(*value)),
+            // int64 is carried as a decimal string in the protobuf JSON mapping
+            AnyValue::Int(value) => ("intValue", Box::new(value.to_string())),
+            AnyValue::Double(value) => ("doubleValue", Box::new(*value)),
+            AnyValue::Bytes(value) => ("bytesValue", Box::new(utilities::base64_encode(value))),
+            AnyValue::Array(values) => ("arrayValue", Box::new(ArrayValue { values: values.clone() })),
+            AnyValue::KvList(values) => ("kvlistValue", Box::new(KeyValueList { values: values.clone() })),
+        };
+        ser::Fragment::Map(Box::new(AnyValueStream { key, value, done: false }))
+    }
+}
+
+impl From<String> for AnyValue {
+    fn from(value: String) -> Self {
+        AnyValue::String(value)
+    }
+}
+
+impl From<&str> for AnyValue {
+    fn from(value: &str) -> Self {
+        AnyValue::String(value.to_string())
+    }
+}
+
+impl From<&String> for AnyValue {
+    fn from(value: &String) -> Self {
+        AnyValue::String(value.clone())
+    }
+}
+
+impl From<bool> for AnyValue {
+    fn from(value: bool) -> Self {
+        AnyValue::Bool(value)
+    }
+}
+
+impl From<i64> for AnyValue {
+    fn from(value: i64) -> Self {
+        AnyValue::Int(value)
+    }
+}
+
+impl From<i32> for AnyValue {
+    fn from(value: i32) -> Self {
+        AnyValue::Int(value as i64)
+    }
+}
+
+impl From<u32> for AnyValue {
+    fn from(value: u32) -> Self {
+        AnyValue::Int(value as i64)
+    }
+}
+
+impl From<f64> for AnyValue {
+    fn from(value: f64) -> Self {
+        AnyValue::Double(value)
+    }
+}
+
+impl From<std::time::Duration> for AnyValue {
+    fn from(value: std::time::Duration) -> Self {
+        AnyValue::Double(value.as_secs_f64())
+    }
+}
+
+impl From<Vec<AnyValue>> for AnyValue {
+    fn from(values: Vec<AnyValue>) -> Self {
+        AnyValue::Array(values)
+    }
+}
+
+impl From<Vec<Attribute>> for AnyValue {
+    fn from(values: Vec<Attribute>) -> Self {
+        AnyValue::KvList(values)
+    }
 }
 
 #[derive(Serialize)]
@@ -125,13 +255,13 @@ pub struct Status {
 
 pub struct Attributes(pub Vec<Attribute>);
 
-impl From<HashMap<String, String>> for Attributes {
-    fn from(map: HashMap<String, String>) -> Self {
+impl<V: Into<AnyValue>> From<HashMap<String, V>> for Attributes {
+    fn from(map: HashMap<String, V>) -> Self {
         Attributes(
             map.into_iter()
-                .map(|(key, string_value)| Attribute {
+                .map(|(key, value)| Attribute {
                     key,
-                    value: AttributeValue { string_value },
+                    value: value.into(),
                 })
                 .collect()
         )
@@ -157,12 +287,60 @@ pub struct ScopeMetrics {
     pub metrics: Vec<Metric>,
 }
 
-#[derive(Serialize)]
 pub struct Metric {
     pub name: String,
     pub description: String,
     pub unit: String,
-    pub sum: Sum,
+    pub data: MetricData,
+}
+
+/// The `data` oneof of an OTLP metric, serialized under the key of the selected variant
+pub enum MetricData {
+    Gauge(GaugeData),
+    Sum(Sum),
+    Histogram(HistogramData),
+}
+
+struct MetricStream<'a> {
+    metric: &'a Metric,
+    state: usize,
+}
+
+impl ser::Map for MetricStream<'_> {
+    fn next(&mut self) -> Option<(Cow<str>, &dyn Serialize)> {
+        let state = self.state;
+        self.state += 1;
+        match state {
+            0 => Some((Cow::Borrowed("name"), &self.metric.name)),
+            1 => Some((Cow::Borrowed("description"), &self.metric.description)),
+            2 => Some((Cow::Borrowed("unit"), &self.metric.unit)),
+            3 => Some(match &self.metric.data {
+                MetricData::Gauge(gauge) => (Cow::Borrowed("gauge"), gauge as &dyn Serialize),
+                MetricData::Sum(sum) => (Cow::Borrowed("sum"), sum as &dyn Serialize),
+                MetricData::Histogram(histogram) => (Cow::Borrowed("histogram"), histogram as &dyn Serialize),
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl Serialize for Metric {
+    fn begin(&self) -> ser::Fragment {
+        ser::Fragment::Map(Box::new(MetricStream { metric: self, state: 0 }))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(i64)]
+pub enum Temporality {
+    Delta = 1,
+    Cumulative = 2,
+}
+
+#[derive(Serialize)]
+pub struct GaugeData {
+    #[serde(rename = "dataPoints")]
+    pub data_points: Vec<DataPoint>,
 }
 
 #[derive(Serialize)]
@@ -175,13 +353,183 @@ pub struct Sum {
     pub data_points: Vec<DataPoint>,
 }
 
-#[derive(Serialize)]
 pub struct DataPoint {
     pub attributes: Vec<Attribute>,
-    #[serde(rename = "startTimeUnixNano")]
     pub start_time_unix_nano: String,
+    pub time_unix_nano: String,
+    pub value: NumberValue,
+}
+
+/// The `value` oneof of a number data point
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NumberValue {
+    Int(i64),
+    Double(f64),
+}
+
+struct DataPointStream<'a> {
+    data_point: &'a DataPoint,
+    int_value: String,
+    state: usize,
+}
+
+impl ser::Map for DataPointStream<'_> {
+    fn next(&mut self) -> Option<(Cow<str>, &dyn Serialize)> {
+        let state = self.state;
+        self.state += 1;
+        match state {
+            0 => Some((Cow::Borrowed("attributes"), &self.data_point.attributes)),
+            1 => Some((Cow::Borrowed("startTimeUnixNano"), &self.data_point.start_time_unix_nano)),
+            2 => Some((Cow::Borrowed("timeUnixNano"), &self.data_point.time_unix_nano)),
+            3 => Some(match &self.data_point.value {
+                NumberValue::Int(_) => (Cow::Borrowed("asInt"), &self.int_value as &dyn Serialize),
+                NumberValue::Double(value) => (Cow::Borrowed("asDouble"), value as &dyn Serialize),
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl Serialize for DataPoint {
+    fn begin(&self) -> ser::Fragment {
+        // int64 is carried as a decimal string in the protobuf JSON mapping
+        let int_value = match self.value {
+            NumberValue::Int(value) => value.to_string(),
+            NumberValue::Double(_) => String::new(),
+        };
+        ser::Fragment::Map(Box::new(DataPointStream { data_point: self, int_value, state: 0 }))
+    }
+}
+
+#[derive(Serialize)]
+pub struct HistogramData {
+    #[serde(rename = "aggregationTemporality")]
+    pub aggregation_temporality: i64,
+    #[serde(rename = "dataPoints")]
+    pub data_points: Vec<HistogramDataPoint>,
+}
+
+pub struct HistogramDataPoint {
+    pub attributes: Vec<Attribute>,
+    pub start_time_unix_nano: String,
+    pub time_unix_nano: String,
+    pub count: u64,
+    pub sum: f64,
+    pub bucket_counts: Vec<u64>,
+    pub explicit_bounds: Vec<f64>,
+    /// Unset while the histogram has no recordings
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+struct HistogramDataPointStream<'a> {
+    data_point: &'a HistogramDataPoint,
+    state: usize,
+}
+
+impl ser::Map for HistogramDataPointStream<'_> {
+    fn next(&mut self) -> Option<(Cow<str>, &dyn Serialize)> {
+        let data_point = self.data_point;
+        loop {
+            let state = self.state;
+            self.state += 1;
+            return match state {
+                0 => Some((Cow::Borrowed("attributes"), &data_point.attributes)),
+                1 => Some((Cow::Borrowed("startTimeUnixNano"), &data_point.start_time_unix_nano)),
+                2 => Some((Cow::Borrowed("timeUnixNano"), &data_point.time_unix_nano)),
+                3 => Some((Cow::Borrowed("count"), &data_point.count)),
+                4 => Some((Cow::Borrowed("sum"), &data_point.sum)),
+                5 => Some((Cow::Borrowed("bucketCounts"), &data_point.bucket_counts)),
+                6 => Some((Cow::Borrowed("explicitBounds"), &data_point.explicit_bounds)),
+                // min and max are optional fields, left out entirely rather than written as null
+                7 => match &data_point.min {
+                    Some(min) => Some((Cow::Borrowed("min"), min)),
+                    None => continue,
+                },
+                8 => match &data_point.max {
+                    Some(max) => Some((Cow::Borrowed("max"), max)),
+                    None => continue,
+                },
+                _ => None,
+            };
+        }
+    }
+}
+
+impl Serialize for HistogramDataPoint {
+    fn begin(&self) -> ser::Fragment {
+        ser::Fragment::Map(Box::new(HistogramDataPointStream { data_point: self, state: 0 }))
+    }
+}
+
+#[derive(Serialize)]
+pub struct ResourceLogsRoot {
+    #[serde(rename = "resourceLogs")]
+    pub resource_logs: Vec<ResourceLogs>,
+}
+
+#[derive(Serialize)]
+pub struct ResourceLogs {
+    pub resource: Resource,
+    #[serde(rename = "scopeLogs")]
+    pub scope_logs: Vec<ScopeLogs>,
+}
+
+#[derive(Serialize)]
+pub struct ScopeLogs {
+    pub scope: Scope,
+    #[serde(rename = "logRecords")]
+    pub log_records: Vec<LogRecord>,
+}
+
+#[derive(Serialize)]
+pub struct LogRecord {
     #[serde(rename = "timeUnixNano")]
     pub time_unix_nano: String,
-    #[serde(rename = "asDouble")]
-    pub as_double: i64,
+    #[serde(rename = "observedTimeUnixNano")]
+    pub observed_time_unix_nano: String,
+    #[serde(rename = "severityNumber")]
+    pub severity_number: i64,
+    #[serde(rename = "severityText")]
+    pub severity_text: String,
+    pub body: AnyValue,
+    pub attributes: Vec<Attribute>,
+    #[serde(rename = "droppedAttributesCount")]
+    pub dropped_attributes_count: i64,
+    pub flags: i64,
+    #[serde(rename = "traceId")]
+    pub trace_id: String,
+    #[serde(rename = "spanId")]
+    pub span_id: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn histogram_data_point(count: u64, min: Option<f64>, max: Option<f64>) -> HistogramDataPoint {
+        HistogramDataPoint {
+            attributes: vec![],
+            start_time_unix_nano: "1".to_string(),
+            time_unix_nano: "2".to_string(),
+            count,
+            sum: 4.0,
+            bucket_counts: vec![count],
+            explicit_bounds: vec![],
+            min,
+            max,
+        }
+    }
+
+    #[test]
+    fn empty_histogram_data_point_omits_min_and_max() {
+        let json = miniserde::json::to_string(&histogram_data_point(0, None, None));
+        assert!(!json.contains("\"min\"") && !json.contains("\"max\""), "{}", json);
+    }
+
+    #[test]
+    fn histogram_data_point_includes_min_and_max() {
+        let json = miniserde::json::to_string(&histogram_data_point(2, Some(1.0), Some(3.0)));
+        assert!(json.ends_with(r#""explicitBounds":[],"min":1.0,"max":3.0}"#), "{}", json);
+    }
 }
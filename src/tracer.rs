@@ -1,80 +1,1054 @@
-use std::sync::Arc;
+use std::future::Future;
+use std::io::Write;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::sync::{Arc, Weak};
+use std::sync::Mutex as SyncMutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 
+use flate2::write::GzEncoder;
 use http::{Request, StatusCode, Uri};
 use http_client::HttpClient;
 use simple_error::{box_err, SimpleResult};
+use smol::{Executor, Timer};
 
+use crate::batch_processor::{self, BatchConfig, BatchQueue, FlushReport, PendingExports};
+use crate::connection_pool::ConnectionPool;
+use crate::env_config::{self, Signal};
+use crate::instrument;
+use crate::meter_provider::MeterProvider;
+use crate::protobuf::ProtoEncode;
+use crate::resource::ResourceDetector;
+use crate::response::{self, PartialSuccess, PartialSuccessHandler};
+use crate::retry::{self, AttemptError, RetryConfig};
 use crate::span_builder::SpanBuilder;
 use crate::structs::*;
 
+/// Idle connections kept per collector endpoint by default
+const DEFAULT_MAX_IDLE_CONNECTIONS: usize = 2;
+
+/// Idle connections older than this are not reused by default, below the common 60s server keep-alive timeouts
+const DEFAULT_IDLE_CONNECTION_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Wire encoding used for export request bodies
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Protocol {
+    #[default]
+    HttpJson,
+    HttpProtobuf,
+}
+
+impl Protocol {
+    fn content_type(&self) -> &'static str {
+        match self {
+            Protocol::HttpJson => "application/json",
+            Protocol::HttpProtobuf => "application/x-protobuf",
+        }
+    }
+}
+
+/// Content encoding applied to export request bodies
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+    /// Needs the `zstd` feature, exports using it fail without
+    Zstd,
+}
+
+impl Compression {
+    fn content_encoding(&self) -> Option<&'static str> {
+        match self {
+            Compression::None => None,
+            Compression::Gzip => Some("gzip"),
+            Compression::Zstd => Some("zstd"),
+        }
+    }
+
+    fn compress(&self, body: Vec<u8>) -> SimpleResult<Vec<u8>> {
+        match self {
+            Compression::None => Ok(body),
+            Compression::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(&body)?;
+                Ok(encoder.finish()?)
+            }
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Ok(zstd::encode_all(body.as_slice(), 0)?),
+            #[cfg(not(feature = "zstd"))]
+            Compression::Zstd => Err(box_err!("zstd compression requires the `zstd` feature")),
+        }
+    }
+}
+
+/// Export settings that can differ between traces, metrics and logs
+#[derive(Clone, Debug)]
+pub struct ExportConfig {
+    pub headers: Vec<(String, String)>,
+    pub protocol: Protocol,
+    /// Maximum time for a single export attempt. A timed out attempt is retried like a connection error until
+    /// [`RetryConfig::max_elapsed_time`] runs out.
+    pub timeout: Duration,
+    pub compression: Compression,
+}
+
+impl Default for ExportConfig {
+    fn default() -> Self {
+        Self {
+            headers: Vec::new(),
+            protocol: Protocol::default(),
+            timeout: Duration::from_secs(10),
+            compression: Compression::default(),
+        }
+    }
+}
+
+type UploadFuture<'a> = Pin<Box<dyn Future<Output = SimpleResult<()>> + Send + 'a>>;
+
+/// Item types exported in batches through one of the tracer's queues
+/// Holds the background export slot of `T`'s queue, releasing it when dropped
+struct ExportInFlight<T: Batched> {
+    tracer: Arc<OtlpTracer>,
+    batched: PhantomData<fn() -> T>,
+}
+
+impl<T: Batched> ExportInFlight<T> {
+    fn new(tracer: Arc<OtlpTracer>) -> Self {
+        Self { tracer, batched: PhantomData }
+    }
+}
+
+impl<T: Batched> Drop for ExportInFlight<T> {
+    fn drop(&mut self) {
+        T::queue(&self.tracer).finish_export();
+    }
+}
+
+trait Batched: Send + Sized + 'static {
+    const NAME: &'static str;
+
+    fn queue(tracer: &OtlpTracer) -> &BatchQueue<Self>;
+
+    fn upload(tracer: &OtlpTracer, batch: Vec<Self>) -> UploadFuture<'_>;
+}
+
+impl Batched for Span {
+    const NAME: &'static str = "spans";
+
+    fn queue(tracer: &OtlpTracer) -> &BatchQueue<Self> {
+        &tracer.span_queue
+    }
+
+    fn upload(tracer: &OtlpTracer, batch: Vec<Self>) -> UploadFuture<'_> {
+        Box::pin(tracer.upload_spans(batch))
+    }
+}
+
+impl Batched for LogRecord {
+    const NAME: &'static str = "logs";
+
+    fn queue(tracer: &OtlpTracer) -> &BatchQueue<Self> {
+        &tracer.log_queue
+    }
+
+    fn upload(tracer: &OtlpTracer, batch: Vec<Self>) -> UploadFuture<'_> {
+        Box::pin(tracer.upload_log_records(batch))
+    }
+}
+
 #[derive(Debug)]
 pub struct OtlpTracer {
     pub traces_endpoint: Uri,
     pub metrics_endpoint: Uri,
+    pub logs_endpoint: Option<Uri>,
     pub service_name: String,
-    pub headers: String,
+    pub traces_config: ExportConfig,
+    pub metrics_config: ExportConfig,
+    pub logs_config: ExportConfig,
+    /// Built once and shared by every export
+    resource: Resource,
+    /// Set by `OTEL_SDK_DISABLED`, nothing is queued or exported
+    disabled: bool,
+    retry_config: RetryConfig,
+    partial_success_handler: Option<PartialSuccessHandler>,
+    rejected_spans: AtomicU64,
+    rejected_data_points: AtomicU64,
+    rejected_log_records: AtomicU64,
+    connection_pool: ConnectionPool,
+    span_queue: BatchQueue<Span>,
+    log_queue: BatchQueue<LogRecord>,
+    pending_exports: PendingExports,
+    meter_providers: SyncMutex<Vec<Weak<MeterProvider>>>,
 }
 
 impl OtlpTracer {
     pub fn new(traces_endpoint: &str, metrics_endpoint: &str, service_name: &str) -> SimpleResult<Self> {
         let headers= std::env::var("OTEL_EXPORTER_OTLP_HEADERS").unwrap_or("".to_string());
+        let defaults = ExportConfig::default();
+        let config = ExportConfig {
+            headers: env_config::parse_key_values(&headers),
+            timeout: env_config::timeout().unwrap_or(defaults.timeout),
+            ..defaults
+        };
         let traces_endpoint: Uri = traces_endpoint.parse()?;
         let metrics_endpoint: Uri = metrics_endpoint.parse()?;
         Ok(Self { 
             traces_endpoint, 
             metrics_endpoint, 
+            logs_endpoint: None,
             service_name: service_name.to_string(), 
-            headers
+            traces_config: config.clone(),
+            metrics_config: config.clone(),
+            logs_config: config,
+            resource: Resource {
+                attributes: vec![
+                    Attribute::new("telemetry.sdk.language", "rust"),
+                    Attribute::new("telemetry.sdk.version", env!("CARGO_PKG_VERSION")),
+                    Attribute::new("telemetry.sdk.name", "opentelemetry"),
+                    Attribute::new("service.name", service_name),
+                ],
+                dropped_attributes_count: 0,
+            },
+            disabled: false,
+            retry_config: RetryConfig::default(),
+            partial_success_handler: None,
+            rejected_spans: AtomicU64::new(0),
+            rejected_data_points: AtomicU64::new(0),
+            rejected_log_records: AtomicU64::new(0),
+            connection_pool: ConnectionPool::new(DEFAULT_MAX_IDLE_CONNECTIONS, DEFAULT_IDLE_CONNECTION_TIMEOUT),
+            span_queue: BatchQueue::new(BatchConfig::default()),
+            log_queue: BatchQueue::new(BatchConfig::default()),
+            pending_exports: PendingExports::default(),
+            meter_providers: SyncMutex::new(Vec::new()),
         })
     }
 
-    async fn send_request(&self, endpoint: &Uri, body: String, error_context: &str) -> SimpleResult<()> {
+    /// Builds a tracer from the standard `OTEL_*` environment variables, signal specific variables win over general ones
+    pub fn from_env() -> SimpleResult<Self> {
+        let service_name = env_config::service_name();
+        let mut tracer = Self::new(
+            &env_config::endpoint(Signal::Traces),
+            &env_config::endpoint(Signal::Metrics),
+            &service_name,
+        )?.with_logs_endpoint(&env_config::endpoint(Signal::Logs))?;
+
+        tracer.traces_config = env_config::export_config(Signal::Traces);
+        tracer.metrics_config = env_config::export_config(Signal::Metrics);
+        tracer.logs_config = env_config::export_config(Signal::Logs);
+        tracer.disabled = env_config::sdk_disabled();
+        // `service.name` was already resolved, with `OTEL_SERVICE_NAME` taking precedence
+        for (key, value) in env_config::resource_attributes() {
+            if key != "service.name" {
+                tracer = tracer.with_resource_attribute(key, value);
+            }
+        }
+        Ok(tracer)
+    }
+
+    /// Sets a resource attribute, replacing any earlier value for `key`
+    pub fn with_resource_attribute(mut self, key: impl Into<String>, value: impl Into<AnyValue>) -> Self {
+        let attribute = Attribute::new(key, value);
+        self.resource.attributes.retain(|existing| existing.key != attribute.key);
+        self.resource.attributes.push(attribute);
+        self
+    }
+
+    pub fn with_service_version(self, version: &str) -> Self {
+        self.with_resource_attribute("service.version", version)
+    }
+
+    pub fn with_deployment_environment(self, environment: &str) -> Self {
+        self.with_resource_attribute("deployment.environment", environment)
+    }
+
+    pub fn with_service_instance_id(self, instance_id: &str) -> Self {
+        self.with_resource_attribute("service.instance.id", instance_id)
+    }
+
+    /// Adds detected attributes, keys that are already set keep their value
+    pub fn with_resource_detectors(mut self, detectors: &[ResourceDetector]) -> Self {
+        for attribute in detectors.iter().flat_map(|detector| detector.detect()) {
+            if !self.resource.attributes.iter().any(|existing| existing.key == attribute.key) {
+                self.resource.attributes.push(attribute);
+            }
+        }
+        self
+    }
+
+    pub fn resource(&self) -> &Resource {
+        &self.resource
+    }
+
+    /// Sets the protocol for every signal
+    pub fn with_protocol(mut self, protocol: Protocol) -> Self {
+        self.traces_config.protocol = protocol;
+        self.metrics_config.protocol = protocol;
+        self.logs_config.protocol = protocol;
+        self
+    }
+
+    /// Sets the request compression for every signal, use the `*_config` fields to set it per signal
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.traces_config.compression = compression;
+        self.metrics_config.compression = compression;
+        self.logs_config.compression = compression;
+        self
+    }
+
+    /// Enables exporting log records emitted through the `logger` module
+    pub fn with_logs_endpoint(mut self, logs_endpoint: &str) -> SimpleResult<Self> {
+        self.logs_endpoint = Some(logs_endpoint.parse()?);
+        Ok(self)
+    }
+
+    /// Sets the per-attempt timeout for every signal, see [`ExportConfig::timeout`]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.traces_config.timeout = timeout;
+        self.metrics_config.timeout = timeout;
+        self.logs_config.timeout = timeout;
+        self
+    }
+
+    /// Number of idle keep-alive connections kept per endpoint, 0 opens a new connection for every export
+    pub fn with_max_idle_connections(mut self, max_idle_connections: usize) -> Self {
+        self.connection_pool = ConnectionPool::new(max_idle_connections, self.connection_pool.idle_timeout());
+        self
+    }
+
+    /// How long a keep-alive connection may sit idle before it is closed instead of reused, keep this below the
+    /// collector's own idle timeout
+    pub fn with_idle_connection_timeout(mut self, timeout: Duration) -> Self {
+        self.connection_pool = ConnectionPool::new(self.connection_pool.max_idle_per_endpoint(), timeout);
+        self
+    }
+
+    /// Backoff used when the collector is unreachable or asks to retry, see [`RetryConfig::disabled`]
+    pub fn with_retry_config(mut self, config: RetryConfig) -> Self {
+        self.retry_config = config;
+        self
+    }
+
+    /// Called whenever the collector accepts a request but rejects some of its items, instead of logging a warning
+    pub fn with_partial_success_handler(mut self, handler: impl Fn(&PartialSuccess) + Send + Sync + 'static) -> Self {
+        self.partial_success_handler = Some(PartialSuccessHandler(Arc::new(handler)));
+        self
+    }
+
+    pub fn with_batch_config(mut self, config: BatchConfig) -> Self {
+        self.span_queue = BatchQueue::new(config.clone());
+        self.log_queue = BatchQueue::new(config);
+        self
+    }
+
+    /// Number of finished spans discarded because the export queue was full
+    pub fn dropped_spans(&self) -> u64 {
+        self.span_queue.dropped_count()
+    }
+
+    /// Number of log records discarded because the export queue was full
+    pub fn dropped_logs(&self) -> u64 {
+        self.log_queue.dropped_count()
+    }
+
+    /// Number of spans the collector reported as rejected in partial success responses
+    pub fn rejected_spans(&self) -> u64 {
+        self.rejected_spans.load(Ordering::Relaxed)
+    }
+
+    /// Number of metric data points the collector reported as rejected in partial success responses
+    pub fn rejected_data_points(&self) -> u64 {
+        self.rejected_data_points.load(Ordering::Relaxed)
+    }
+
+    /// Number of log records the collector reported as rejected in partial success responses
+    pub fn rejected_log_records(&self) -> u64 {
+        self.rejected_log_records.load(Ordering::Relaxed)
+    }
+
+    fn scope() -> Scope {
+        Scope {
+            name: env!("CARGO_PKG_NAME").to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            attributes: vec![],
+            dropped_attributes_count: 0,
+        }
+    }
+
+    fn encode_body<T: miniserde::Serialize + ProtoEncode>(config: &ExportConfig, root: &T) -> Vec<u8> {
+        match config.protocol {
+            Protocol::HttpJson => miniserde::json::to_string(root).into_bytes(),
+            Protocol::HttpProtobuf => root.encode_to_vec(),
+        }
+    }
+
+    async fn send_request(&self, signal: Signal, endpoint: &Uri, config: &ExportConfig, request_body_bytes: Vec<u8>) -> SimpleResult<()> {
         log::info!("sending request to {}", endpoint);
 
-        let request_body_bytes = body.as_bytes().to_vec();
+        let request_body_bytes = config.compression.compress(request_body_bytes)?;
         let mut request_builder = Request::builder()
             .method("POST")
             .uri(endpoint)
-            .header("Content-Type", "application/json")
+            .header("Content-Type", config.protocol.content_type())
             .header("Content-Length", request_body_bytes.len().to_string())
             .header("Host", endpoint.host().unwrap_or_default());
 
-        if !self.headers.is_empty() {
-            for header in self.headers.split(',') {
-                if let Some((key, value)) = header.split_once('=') {
-                    request_builder = request_builder.header(key.trim(), value.trim());
-                }
-            }
+        if let Some(content_encoding) = config.compression.content_encoding() {
+            request_builder = request_builder.header("Content-Encoding", content_encoding);
+        }
+
+        for (key, value) in &config.headers {
+            request_builder = request_builder.header(key, value);
         }
 
         let request: Request<Vec<u8>> = request_builder.body(request_body_bytes)?;
-        let mut stream = HttpClient::create_connection(&request).await?;
-        let response = HttpClient::request(&mut stream, &request).await?;
+        // The timeout bounds each attempt, a timed out attempt is retried until `max_elapsed_time` runs out
+        let request = &request;
+        let response_body = retry::with_retries(&self.retry_config, || async move {
+            let attempt = self.send_once(request, signal.name());
+            batch_processor::with_deadline(Instant::now() + config.timeout, attempt).await
+                .unwrap_or(Err(AttemptError::Timeout { timeout: config.timeout }))
+        }).await?;
+
+        if let Some((rejected, error_message)) = response::parse_partial_success(config.protocol, &response_body) {
+            self.on_partial_success(signal, PartialSuccess { signal: signal.name(), rejected, error_message });
+        }
+        Ok(())
+    }
+
+    fn on_partial_success(&self, signal: Signal, partial_success: PartialSuccess) {
+        let counter = match signal {
+            Signal::Traces => &self.rejected_spans,
+            Signal::Metrics => &self.rejected_data_points,
+            Signal::Logs => &self.rejected_log_records,
+        };
+        counter.fetch_add(partial_success.rejected, Ordering::Relaxed);
+
+        match &self.partial_success_handler {
+            Some(handler) => (handler.0)(&partial_success),
+            None => log::warn!(
+                "collector rejected {} {}: {}",
+                partial_success.rejected,
+                partial_success.signal,
+                partial_success.error_message,
+            ),
+        }
+    }
+
+    /// Sends `request` once and classifies a failure as retryable or permanent per the OTLP spec
+    async fn send_once(&self, request: &Request<Vec<u8>>, error_context: &str) -> Result<Vec<u8>, AttemptError> {
+        let connection_error = |e: &dyn std::fmt::Display| AttemptError::Retryable {
+            message: format!("failed to upload {}: {}", error_context, e),
+            retry_after: None,
+        };
+
+        // A pooled connection may have been closed by the server while idle, so failures on it just mean reconnecting
+        let pool_key = ConnectionPool::key(request.uri());
+        let mut pooled = self.connection_pool.checkout(&pool_key);
+        let mut pooled_response = None;
+        if let Some(stream) = pooled.as_mut() {
+            match HttpClient::request(stream, request).await {
+                Ok(response) => pooled_response = Some(response),
+                Err(e) => log::debug!("pooled connection to {} failed, reconnecting: {}", pool_key, e),
+            }
+        }
+        let (stream, response) = match (pooled, pooled_response) {
+            (Some(stream), Some(response)) => (stream, response),
+            _ => {
+                let mut stream = HttpClient::create_connection(request).await.map_err(|e| connection_error(&e))?;
+                let response = HttpClient::request(&mut stream, request).await.map_err(|e| connection_error(&e))?;
+                (stream, response)
+            }
+        };
         log::info!("response: {:02x?}", response);
-        let response_body = String::from_utf8(response.body().clone())?;
 
-        if response.status() != StatusCode::OK {
-            return Err(box_err!(format!("failed to upload {}: {} {}", error_context, response.status(), response_body)));
+        let connection_close = response.headers()
+            .get(http::header::CONNECTION)
+            .is_some_and(|value| value.as_bytes().eq_ignore_ascii_case(b"close"));
+        if !connection_close {
+            self.connection_pool.checkin(pool_key, stream);
         }
 
-        Ok(())
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response.body().clone());
+        }
+
+        let message = format!("failed to upload {}: {} {}", error_context, status, String::from_utf8_lossy(response.body()));
+        match status {
+            StatusCode::TOO_MANY_REQUESTS | StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE | StatusCode::GATEWAY_TIMEOUT => {
+                // Only the delay-seconds form of Retry-After is supported
+                let retry_after = response.headers()
+                    .get(http::header::RETRY_AFTER)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.trim().parse::<u64>().ok())
+                    .map(Duration::from_secs);
+                Err(AttemptError::Retryable { message, retry_after })
+            }
+            _ => Err(AttemptError::Permanent { message }),
+        }
     }
 
     pub async fn upload_traces(&self, resource_spans: Vec<ResourceSpan>) -> SimpleResult<()> {
         log::info!("uploading traces");
         let root = ResourceSpansRoot { resource_spans };
-        let request_body = miniserde::json::to_string(&root);
-        self.send_request(&self.traces_endpoint, request_body, "traces").await
+        let request_body = Self::encode_body(&self.traces_config, &root);
+        self.send_request(Signal::Traces, &self.traces_endpoint, &self.traces_config, request_body).await
+    }
+
+    pub(crate) fn on_span_end(self: &Arc<Self>, executor: &Arc<Executor<'static>>, span: Span) {
+        if !self.disabled {
+            self.enqueue(executor, span);
+        }
+    }
+
+    pub(crate) fn on_log(self: &Arc<Self>, executor: &Arc<Executor<'static>>, record: LogRecord) {
+        if !self.disabled && self.logs_endpoint.is_some() {
+            self.enqueue(executor, record);
+        }
+    }
+
+    fn enqueue<T: Batched>(self: &Arc<Self>, executor: &Arc<Executor<'static>>, item: T) {
+        let queue = T::queue(self);
+        let batch_ready = queue.push(item);
+
+        // Periodic flush so small batches do not wait for the size threshold forever
+        queue.start_timer(|| {
+            let tracer = self.clone();
+            let executor_clone = executor.clone();
+            executor.spawn(async move {
+                loop {
+                    Timer::after(T::queue(&tracer).config().scheduled_delay).await;
+                    // A closed queue is flushed by shutdown itself
+                    if T::queue(&tracer).is_closed() {
+                        break;
+                    }
+                    tracer.spawn_exports::<T>(&executor_clone);
+                }
+            })
+        });
+
+        if batch_ready {
+            self.spawn_exports::<T>(executor);
+        }
+    }
+
+    /// Exports the next batch unless one is already in flight. Items stay queued meanwhile, so `max_queue_size`
+    /// bounds memory even while the collector is slow or down.
+    fn spawn_exports<T: Batched>(self: &Arc<Self>, executor: &Arc<Executor<'static>>) {
+        let queue = T::queue(self);
+        if !queue.start_export() {
+            return;
+        }
+        let batch = queue.next_batch();
+        if batch.is_empty() {
+            queue.finish_export();
+            return;
+        }
+
+        // Created outside the task so the slot is released even if the task is cancelled before it runs
+        let in_flight = ExportInFlight::<T>::new(self.clone());
+        let executor_clone = executor.clone();
+        self.spawn_export(executor, T::NAME, batch.len(), async move {
+            let result = T::upload(&in_flight.tracer, batch).await;
+            let tracer = in_flight.tracer.clone();
+            drop(in_flight);
+
+            // Full batches that queued up meanwhile go out right away, smaller ones wait for the timer
+            let queue = T::queue(&tracer);
+            if queue.len() >= queue.config().max_export_batch_size {
+                tracer.spawn_exports::<T>(&executor_clone);
+            }
+            result
+        });
+    }
+
+    /// Runs `upload` on `executor`, tracking it so [`OtlpTracer::force_flush`] waits for it and reports its outcome
+    pub(crate) fn spawn_export<F>(&self, executor: &Executor<'static>, name: &'static str, items: usize, upload: F)
+    where
+        F: Future<Output = SimpleResult<()>> + Send + 'static,
+    {
+        self.pending_exports.spawn(executor, items, async move {
+            // Handle any errors here since there is no caller to propagate them to
+            match instrument::suppressed(upload).await {
+                Ok(()) => true,
+                Err(e) => {
+                    eprintln!("Failed to upload {}: {}", name, e);
+                    false
+                }
+            }
+        });
+    }
+
+    async fn flush_queue<T: Batched>(&self, deadline: Instant) -> FlushReport {
+        let mut report = FlushReport::default();
+
+        loop {
+            let batch = T::queue(self).next_batch();
+            if batch.is_empty() {
+                break;
+            }
+            let items = batch.len();
+            if Instant::now() >= deadline {
+                report.record(items, false);
+                continue;
+            }
+            let upload = instrument::suppressed(T::upload(self, batch));
+            let success = match batch_processor::with_deadline(deadline, upload).await {
+                Some(Ok(())) => true,
+                Some(Err(e)) => {
+                    eprintln!("Failed to upload {}: {}", T::NAME, e);
+                    false
+                }
+                None => false,
+            };
+            report.record(items, success);
+        }
+
+        report
+    }
+
+    /// Called by [`MeterProvider::start`] so metrics are flushed and shut down together with spans and logs
+    pub(crate) fn register_meter_provider(&self, provider: &Arc<MeterProvider>) {
+        self.meter_providers.lock().unwrap().push(Arc::downgrade(provider));
+    }
+
+    fn meter_providers(&self) -> Vec<Arc<MeterProvider>> {
+        let mut providers = self.meter_providers.lock().unwrap();
+        providers.retain(|provider| provider.strong_count() > 0);
+        providers.iter().filter_map(Weak::upgrade).collect()
+    }
+
+    /// Waits for in-flight exports and uploads everything still queued, including a final collection of every
+    /// started [`MeterProvider`], giving up at `timeout`
+    pub async fn force_flush(&self, timeout: Duration) -> FlushReport {
+        let deadline = Instant::now() + timeout;
+        let mut report = self.pending_exports.drain(deadline).await;
+        report.merge(self.flush_queue::<Span>(deadline).await);
+        report.merge(self.flush_queue::<LogRecord>(deadline).await);
+        for provider in self.meter_providers() {
+            report.merge(provider.flush_until(deadline).await);
+        }
+        // Exports started by the flush timer or a full batch while the queues were flushed
+        report.merge(self.pending_exports.drain(deadline).await);
+        report
+    }
+
+    /// Stops accepting new spans and log records, stops every started [`MeterProvider`] and flushes everything
+    /// pending, see [`OtlpTracer::force_flush`]
+    pub async fn shutdown(&self, timeout: Duration) -> FlushReport {
+        self.span_queue.close();
+        self.log_queue.close();
+        self.span_queue.stop_timer().await;
+        self.log_queue.stop_timer().await;
+        for provider in self.meter_providers() {
+            provider.stop().await;
+        }
+        self.force_flush(timeout).await
+    }
+
+    async fn upload_spans(&self, spans: Vec<Span>) -> SimpleResult<()> {
+        let resource_span = ResourceSpan {
+            resource: self.resource.clone(),
+            scope_spans: vec![ScopeSpan {
+                scope: Self::scope(),
+                spans,
+            }],
+        };
+        self.upload_traces(vec![resource_span]).await
     }
 
     pub async fn upload_metrics(&self, resource_metrics: Vec<ResourceMetrics>) -> SimpleResult<()> {
         log::info!("uploading metrics");
         let root = ResourceMetricsRoot { resource_metrics };
-        let request_body = miniserde::json::to_string(&root);
-        self.send_request(&self.metrics_endpoint, request_body, "metrics").await
+        let request_body = Self::encode_body(&self.metrics_config, &root);
+        self.send_request(Signal::Metrics, &self.metrics_endpoint, &self.metrics_config, request_body).await
+    }
+
+    /// Wraps `metrics` in this tracer's resource and scope and uploads them in one request
+    pub(crate) async fn export_metrics(&self, metrics: Vec<Metric>) -> SimpleResult<()> {
+        if self.disabled {
+            return Ok(());
+        }
+        let resource_metrics = ResourceMetrics {
+            resource: self.resource.clone(),
+            scope_metrics: vec![ScopeMetrics {
+                scope: Self::scope(),
+                metrics,
+            }],
+        };
+        self.upload_metrics(vec![resource_metrics]).await
+    }
+
+    pub async fn upload_logs(&self, resource_logs: Vec<ResourceLogs>) -> SimpleResult<()> {
+        log::info!("uploading logs");
+        let Some(logs_endpoint) = &self.logs_endpoint else {
+            return Err(box_err!(format!("failed to upload logs: no logs endpoint configured for {}", self.service_name)));
+        };
+        let root = ResourceLogsRoot { resource_logs };
+        let request_body = Self::encode_body(&self.logs_config, &root);
+        self.send_request(Signal::Logs, logs_endpoint, &self.logs_config, request_body).await
+    }
+
+    async fn upload_log_records(&self, log_records: Vec<LogRecord>) -> SimpleResult<()> {
+        let resource_logs = ResourceLogs {
+            resource: self.resource.clone(),
+            scope_logs: vec![ScopeLogs {
+                scope: Self::scope(),
+                log_records,
+            }],
+        };
+        self.upload_logs(vec![resource_logs]).await
     }
 
     pub fn span(self: &Arc<Self>, name: &str) -> SpanBuilder {
         SpanBuilder::new(self.clone(), name)
     }
+
+    /// Removes and returns every span waiting for export
+    #[cfg(test)]
+    pub(crate) fn take_queued_spans(&self) -> Vec<Span> {
+        let mut spans = Vec::new();
+        loop {
+            let batch = self.span_queue.next_batch();
+            if batch.is_empty() {
+                return spans;
+            }
+            spans.extend(batch);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::io::{BufRead, BufReader, Read};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::mpsc::{self, Receiver, Sender};
+
+    use super::*;
+    use crate::span_context::SpanContext;
+    use crate::span_guard::SpanGuard;
+    use crate::utilities;
+
+    struct ReceivedRequest {
+        headers: Vec<(String, String)>,
+        body: Vec<u8>,
+    }
+
+    impl ReceivedRequest {
+        fn header(&self, name: &str) -> Option<&str> {
+            self.headers.iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.as_str())
+        }
+    }
+
+    /// Stand-in collector on a local port, answering every request with an empty 200
+    struct Collector {
+        endpoint: String,
+        requests: Receiver<ReceivedRequest>,
+        /// Sends one message per accepted connection
+        connections: Receiver<()>,
+    }
+
+    impl Collector {
+        fn start() -> Self {
+            Self::start_with(false)
+        }
+
+        /// `close_after_response` closes each connection after answering, without announcing it with
+        /// `Connection: close`, like a server whose idle timeout expired
+        fn start_with(close_after_response: bool) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let endpoint = format!("http://{}", listener.local_addr().unwrap());
+            let (request_sender, requests) = mpsc::channel();
+            let (connection_sender, connections) = mpsc::channel();
+            std::thread::spawn(move || {
+                for stream in listener.incoming() {
+                    let Ok(stream) = stream else { break };
+                    if connection_sender.send(()).is_err() {
+                        break;
+                    }
+                    let request_sender = request_sender.clone();
+                    std::thread::spawn(move || Self::serve(stream, request_sender, close_after_response));
+                }
+            });
+            Self { endpoint, requests, connections }
+        }
+
+        fn serve(stream: TcpStream, requests: Sender<ReceivedRequest>, close_after_response: bool) {
+            let mut writer = stream.try_clone().unwrap();
+            let mut reader = BufReader::new(stream);
+            while let Some(request) = Self::read_request(&mut reader) {
+                // Recorded before answering, so the request is visible once the export returns
+                if requests.send(request).is_err() {
+                    break;
+                }
+                writer.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n").unwrap();
+                if close_after_response {
+                    break;
+                }
+            }
+        }
+
+        fn read_request(reader: &mut impl BufRead) -> Option<ReceivedRequest> {
+            let mut line = String::new();
+            reader.read_line(&mut line).ok().filter(|read| *read > 0)?;
+
+            let mut headers = Vec::new();
+            loop {
+                line.clear();
+                reader.read_line(&mut line).ok()?;
+                let Some((key, value)) = line.trim_end().split_once(':') else { break };
+                headers.push((key.trim().to_string(), value.trim().to_string()));
+            }
+
+            let length = headers.iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case("content-length"))
+                .and_then(|(_, value)| value.parse().ok())
+                .unwrap_or(0);
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).ok()?;
+            Some(ReceivedRequest { headers, body })
+        }
+
+        fn tracer(&self) -> OtlpTracer {
+            tracer(&self.endpoint).with_retry_config(RetryConfig::disabled())
+        }
+
+        fn request(&self) -> ReceivedRequest {
+            self.requests.recv_timeout(Duration::from_secs(5)).unwrap()
+        }
+
+        fn accepted_connections(&self) -> usize {
+            self.connections.try_iter().count()
+        }
+    }
+
+    fn resource_spans() -> ResourceSpansRoot {
+        ResourceSpansRoot {
+            resource_spans: vec![ResourceSpan {
+                resource: Resource {
+                    attributes: vec![Attribute::new("service.name", "test")],
+                    dropped_attributes_count: 0,
+                },
+                scope_spans: vec![],
+            }],
+        }
+    }
+
+    /// Exports `resource_spans()` and returns the body the collector received, decompressed per its
+    /// `Content-Encoding`
+    fn export_compressed(tracer: OtlpTracer, collector: &Collector, expected_encoding: &str) -> Vec<u8> {
+        smol::block_on(tracer.upload_traces(resource_spans().resource_spans)).unwrap();
+        let request = collector.request();
+        assert_eq!(request.header("content-encoding"), Some(expected_encoding));
+        assert_eq!(request.header("content-length"), Some(request.body.len().to_string().as_str()));
+        match expected_encoding {
+            "gzip" => {
+                let mut body = Vec::new();
+                flate2::read::GzDecoder::new(request.body.as_slice()).read_to_end(&mut body).unwrap();
+                body
+            }
+            #[cfg(feature = "zstd")]
+            "zstd" => zstd::decode_all(request.body.as_slice()).unwrap(),
+            other => panic!("unexpected content encoding {}", other),
+        }
+    }
+
+    #[test]
+    fn gzip_compresses_json_body() {
+        let collector = Collector::start();
+        let tracer = collector.tracer().with_compression(Compression::Gzip);
+        let body = export_compressed(tracer, &collector, "gzip");
+        assert_eq!(body, miniserde::json::to_string(&resource_spans()).into_bytes());
+    }
+
+    #[test]
+    fn gzip_compresses_protobuf_body() {
+        let collector = Collector::start();
+        let tracer = collector.tracer().with_protocol(Protocol::HttpProtobuf).with_compression(Compression::Gzip);
+        let body = export_compressed(tracer, &collector, "gzip");
+        assert_eq!(body, resource_spans().encode_to_vec());
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn zstd_compresses_protobuf_body() {
+        let collector = Collector::start();
+        let tracer = collector.tracer().with_protocol(Protocol::HttpProtobuf).with_compression(Compression::Zstd);
+        let body = export_compressed(tracer, &collector, "zstd");
+        assert_eq!(body, resource_spans().encode_to_vec());
+    }
+
+    #[cfg(not(feature = "zstd"))]
+    #[test]
+    fn zstd_fails_without_the_feature() {
+        assert!(Compression::Zstd.compress(b"body".to_vec()).is_err());
+    }
+
+    #[test]
+    fn uncompressed_body_has_no_content_encoding() {
+        let collector = Collector::start();
+        smol::block_on(collector.tracer().upload_traces(resource_spans().resource_spans)).unwrap();
+        let request = collector.request();
+        assert_eq!(request.header("content-encoding"), None);
+        assert_eq!(request.body, miniserde::json::to_string(&resource_spans()).into_bytes());
+    }
+
+    fn tracer(endpoint: &str) -> OtlpTracer {
+        OtlpTracer::new(&format!("{}/v1/traces", endpoint), &format!("{}/v1/metrics", endpoint), "test").unwrap()
+    }
+
+    fn start_span(executor: &Arc<Executor<'static>>, tracer: &Arc<OtlpTracer>, remote_parent: Option<SpanContext>) -> Arc<SpanGuard> {
+        let status = Status {
+            message: String::new(),
+            code: crate::structs::StatusCode::Unset as i64,
+        };
+        SpanGuard::start(executor, tracer, "request", status, SpanKind::Server, HashMap::new(), remote_parent)
+    }
+
+    #[test]
+    fn unsampled_spans_are_not_exported() {
+        let executor = Arc::new(Executor::new());
+        let tracer = Arc::new(tracer("http://localhost:4318"));
+        let remote_parent = |trace_flags| SpanContext {
+            trace_id: "0af7651916cd43dd8448eb211c80319c".to_string(),
+            span_id: "b7ad6b7169203331".to_string(),
+            trace_flags,
+            trace_state: String::new(),
+            is_remote: true,
+        };
+
+        drop(start_span(&executor, &tracer, Some(remote_parent(0x00))));
+        assert_eq!(tracer.span_queue.len(), 0);
+
+        drop(start_span(&executor, &tracer, Some(remote_parent(0x01))));
+        drop(start_span(&executor, &tracer, None));
+        assert_eq!(tracer.span_queue.len(), 2);
+    }
+
+    /// Exports twice through the same tracer, waiting for the collector to see each request
+    fn export_twice(tracer: &OtlpTracer, collector: &Collector) {
+        for _ in 0..2 {
+            smol::block_on(tracer.upload_traces(resource_spans().resource_spans)).unwrap();
+            collector.request();
+        }
+    }
+
+    #[test]
+    fn consecutive_exports_reuse_the_connection() {
+        let collector = Collector::start();
+        export_twice(&collector.tracer(), &collector);
+        assert_eq!(collector.accepted_connections(), 1);
+    }
+
+    #[test]
+    fn reconnects_when_the_server_closed_the_idle_connection() {
+        let collector = Collector::start_with(true);
+        // Retries are disabled, so the second export only succeeds if the reconnect happens within one attempt
+        export_twice(&collector.tracer(), &collector);
+        assert_eq!(collector.accepted_connections(), 2);
+    }
+
+    #[test]
+    fn idle_connections_expire_after_the_idle_timeout() {
+        let collector = Collector::start();
+        let tracer = collector.tracer().with_idle_connection_timeout(Duration::ZERO);
+        export_twice(&tracer, &collector);
+        assert_eq!(collector.accepted_connections(), 2);
+    }
+
+    #[test]
+    fn no_idle_connections_opens_one_per_export() {
+        let collector = Collector::start();
+        let tracer = collector.tracer().with_max_idle_connections(0);
+        export_twice(&tracer, &collector);
+        assert_eq!(collector.accepted_connections(), 2);
+    }
+
+    fn span(name: &str) -> Span {
+        Span {
+            trace_id: utilities::generate_trace_id(),
+            span_id: utilities::generate_span_id(),
+            parent_span_id: String::new(),
+            name: name.to_string(),
+            start_time_unix_nano: "1".to_string(),
+            end_time_unix_nano: "2".to_string(),
+            kind: SpanKind::Internal as i64,
+            attributes: vec![],
+            events: vec![],
+            trace_state: String::new(),
+            flags: 0,
+            dropped_attributes_count: 0,
+            dropped_events_count: 0,
+            links: vec![],
+            dropped_links_count: 0,
+            status: Status {
+                message: String::new(),
+                code: crate::structs::StatusCode::Unset as i64,
+            },
+        }
+    }
+
+    /// Executor driven on its own thread, like an application's runtime
+    fn background_executor() -> Arc<Executor<'static>> {
+        let executor = Arc::new(Executor::new());
+        let runner = executor.clone();
+        std::thread::spawn(move || smol::block_on(runner.run(smol::future::pending::<()>())));
+        executor
+    }
+
+    /// Spans in every request the collector has received so far
+    fn received_spans(collector: &Collector) -> usize {
+        collector.requests.try_iter()
+            .map(|request| String::from_utf8_lossy(&request.body).matches("\"spanId\"").count())
+            .sum()
+    }
+
+    #[test]
+    fn shutdown_waits_for_every_queued_span() {
+        let collector = Collector::start();
+        let executor = background_executor();
+        // A 1ms flush timer keeps spawning exports while shutdown runs
+        let tracer = Arc::new(collector.tracer().with_batch_config(BatchConfig {
+            max_queue_size: 100,
+            max_export_batch_size: 2,
+            scheduled_delay: Duration::from_millis(1),
+        }));
+
+        for i in 0..25 {
+            tracer.on_span_end(&executor, span(&format!("span-{}", i)));
+        }
+        let report = smol::block_on(tracer.shutdown(Duration::from_secs(5)));
+        assert_eq!(report, FlushReport { exported: 25, failed: 0 });
+        assert_eq!(received_spans(&collector), 25);
+    }
+
+    #[test]
+    fn one_background_export_in_flight_per_signal() {
+        // A collector that accepts connections but never answers
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let executor = background_executor();
+        let tracer = Arc::new(tracer(&endpoint).with_batch_config(BatchConfig {
+            max_queue_size: 10,
+            max_export_batch_size: 2,
+            scheduled_delay: Duration::from_secs(60),
+        }));
+
+        // The first two spans leave with the first export, the next ten fill the queue and the rest are dropped
+        for i in 0..20 {
+            tracer.on_span_end(&executor, span(&format!("span-{}", i)));
+        }
+        let _connection = listener.accept().unwrap();
+        assert_eq!(tracer.span_queue.len(), 10);
+        assert_eq!(tracer.dropped_spans(), 8);
+
+        std::thread::sleep(Duration::from_millis(50));
+        listener.set_nonblocking(true).unwrap();
+        assert!(listener.accept().is_err(), "a second export started while the first was in flight");
+    }
 }
@@ -0,0 +1,79 @@
+use std::sync::Arc;
+
+use crate::meter_provider::Collect;
+use crate::structs::{AnyValue, Attribute, Metric, Temporality};
+use crate::tracer::OtlpTracer;
+use crate::metric_base::MetricBase;
+use crate::number::Number;
+
+/// Non-monotonic sum for values that go up and down, like active connections
+pub struct UpDownCounter<T: Number = i64> {
+    base: MetricBase<T>,
+}
+
+impl UpDownCounter<i64> {
+    pub fn new(
+        tracer: Arc<OtlpTracer>,
+        name: &str,
+        description: &str,
+        unit: &str,
+    ) -> Self {
+        Self {
+            base: MetricBase::new(tracer, name, description, unit),
+        }
+    }
+}
+
+impl UpDownCounter<f64> {
+    pub fn new_f64(
+        tracer: Arc<OtlpTracer>,
+        name: &str,
+        description: &str,
+        unit: &str,
+    ) -> Self {
+        Self {
+            base: MetricBase::new(tracer, name, description, unit),
+        }
+    }
+}
+
+impl<T: Number> UpDownCounter<T> {
+    pub fn with_attribute(mut self, key: impl Into<String>, value: impl Into<AnyValue>) -> Self {
+        self.base.with_attribute(key, value);
+        self
+    }
+
+    pub fn with_temporality(mut self, temporality: Temporality) -> Self {
+        self.base.with_temporality(temporality);
+        self
+    }
+
+    /// Caps the number of distinct attribute sets, further ones are aggregated into an overflow series
+    pub fn with_cardinality_limit(mut self, limit: usize) -> Self {
+        self.base.with_cardinality_limit(limit);
+        self
+    }
+
+    pub fn inc(&self) {
+        self.add(T::ONE, &[]);
+    }
+
+    pub fn dec(&self) {
+        self.add(-T::ONE, &[]);
+    }
+
+    /// Adds `value`, which may be negative, to the series identified by `attributes`
+    pub fn add(&self, value: T, attributes: &[Attribute]) {
+        self.base.add_value(value, attributes);
+    }
+
+    pub async fn upload(&self) -> simple_error::SimpleResult<()> {
+        self.base.upload(self.collect()).await
+    }
+}
+
+impl<T: Number> Collect for UpDownCounter<T> {
+    fn collect(&self) -> Metric {
+        self.base.sum_metric(false)
+    }
+}